@@ -305,6 +305,7 @@ async fn example_custom_options() -> Result<(), Box<dyn std::error::Error>> {
         desc_truncate_len: 200,     // Shorter description
         desc_len_threshold: 100,    // Lower threshold
         content_len_threshold: 150, // Lower threshold
+        ..Default::default()
     };
 
     let html = r#"
@@ -0,0 +1,259 @@
+use crate::canonical::same_url;
+use crate::error::{AppResult, Error};
+use crate::linker::absolutify;
+use crate::parse_from_html::{parse_from_html, ParseOptions, ParsedContent};
+use crate::retrieve::{retrieve_html, RetrieveOptions};
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Components without any content worth keeping: ads, analytics, consent...
+const DROPPED: &[&str] = &[
+    "amp-ad",
+    "amp-analytics",
+    "amp-auto-ads",
+    "amp-consent",
+    "amp-embed",
+    "amp-geo",
+    "amp-pixel",
+    "amp-sticky-ad",
+    "amp-user-notification",
+];
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+// Elements whose text html5ever keeps unescaped
+const RAW_TEXT: &[&str] = &["script", "style", "noscript", "iframe", "xmp", "noembed", "noframes"];
+
+/// Whether the page is an AMP document (`<html amp>` or `<html ⚡>`)
+pub fn is_amp_document(html: &str) -> bool {
+    let document = Html::parse_document(html);
+    let element = document.root_element();
+    element.attr("amp").is_some() || element.attr("⚡").is_some()
+}
+
+/// Absolute URL of the `<link rel="amphtml">` of a page
+pub fn amp_url(html: &str, base_url: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel][href]").unwrap();
+    document
+        .select(&selector)
+        .find(|link| {
+            link.attr("rel")
+                .unwrap_or("")
+                .split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("amphtml"))
+        })
+        .map(|link| absolutify(base_url, link.attr("href").unwrap_or("").trim()))
+        .filter(|url| !url.is_empty())
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_attr(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+// ` name="value"` for each of `names` the element carries
+fn copy_attrs(element: &Element, names: &[&str]) -> String {
+    names
+        .iter()
+        .filter_map(|&name| element.attr(name).map(|value| format!(" {}=\"{}\"", name, escape_attr(value))))
+        .collect()
+}
+
+fn embed(src: &str, element: &Element) -> String {
+    format!(
+        "<iframe src=\"{}\"{} frameborder=\"0\" allowfullscreen></iframe>",
+        escape_attr(src),
+        copy_attrs(element, &["width", "height"])
+    )
+}
+
+// Standard HTML for an AMP component, `None` for elements that are not one
+fn convert(element: ElementRef) -> Option<String> {
+    let value = element.value();
+    let html = match value.name() {
+        name if DROPPED.contains(&name) => String::new(),
+        "amp-img" | "amp-anim" => {
+            format!("<img{}>", copy_attrs(value, &["src", "srcset", "sizes", "alt", "title", "width", "height"]))
+        }
+        name @ ("amp-video" | "amp-audio") => {
+            let tag = &name[4..];
+            // Keep the sources, not the fallback and placeholder children
+            let sources: String = element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|child| matches!(child.value().name(), "source" | "track"))
+                .map(|child| render(*child))
+                .collect();
+            format!(
+                "<{tag} controls{}>{}</{tag}>",
+                copy_attrs(value, &["src", "poster", "width", "height"]),
+                sources
+            )
+        }
+        "amp-iframe" => format!(
+            "<iframe{}></iframe>",
+            copy_attrs(value, &["src", "width", "height", "frameborder", "allow", "allowfullscreen"])
+        ),
+        "amp-youtube" => {
+            let id = value.attr("data-videoid")?;
+            embed(&format!("https://www.youtube.com/embed/{}", id), value)
+        }
+        "amp-vimeo" => {
+            let id = value.attr("data-videoid")?;
+            embed(&format!("https://player.vimeo.com/video/{}", id), value)
+        }
+        // Slides follow each other
+        "amp-carousel" => format!("<div>{}</div>", element.children().map(render).collect::<String>()),
+        _ => return None,
+    };
+    Some(html)
+}
+
+fn render(node: ego_tree::NodeRef<Node>) -> String {
+    match node.value() {
+        Node::Doctype(_) => "<!DOCTYPE html>".to_string(),
+        Node::Comment(comment) => format!("<!--{}-->", &**comment),
+        Node::Text(text) => {
+            let raw = node
+                .parent()
+                .and_then(ElementRef::wrap)
+                .is_some_and(|parent| RAW_TEXT.contains(&parent.value().name()));
+            if raw {
+                text.to_string()
+            } else {
+                escape_text(text)
+            }
+        }
+        Node::Element(element) => {
+            let element_ref = ElementRef::wrap(node).unwrap();
+            if let Some(html) = convert(element_ref) {
+                return html;
+            }
+            let name = element.name();
+            let attrs: String = element
+                .attrs()
+                .map(|(key, value)| format!(" {}=\"{}\"", key, escape_attr(value)))
+                .collect();
+            if VOID.contains(&name) {
+                return format!("<{}{}>", name, attrs);
+            }
+            let children: String = node.children().map(render).collect();
+            format!("<{}{}>{}</{}>", name, attrs, children, name)
+        }
+        Node::Document | Node::Fragment => node.children().map(render).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Replace AMP components with the standard elements the sanitizer keeps:
+/// `amp-img` and `amp-anim` become `<img>`, `amp-video` and `amp-audio` their
+/// HTML counterparts, `amp-iframe`, `amp-youtube` and `amp-vimeo` iframes, and
+/// `amp-carousel` a `<div>` of its slides. Ads and analytics are dropped.
+pub fn normalize_amp(html: &str) -> String {
+    let document = Html::parse_document(html);
+    render(document.tree.root())
+}
+
+/// The AMP version of a page, parsed
+pub(crate) struct AmpPage {
+    pub url: String,
+    pub html: String,
+    pub parsed: ParsedContent,
+}
+
+/// Fetch and parse the AMP version of a page whose extraction failed or scored
+/// below `ParseOptions::amp_confidence`.
+///
+/// `None` when the page has no AMP version or it does no better. The URL chosen
+/// for the main page is kept.
+pub(crate) async fn amp_fallback(
+    main: &AppResult<ParsedContent>,
+    html: &str,
+    input_url: &str,
+    parser_opts: &ParseOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> Option<AmpPage> {
+    let main = match main {
+        Ok(parsed) if parsed.quality.confidence >= parser_opts.amp_confidence => return None,
+        Ok(parsed) => Some(parsed),
+        // A listing or home page has no better AMP twin
+        Err(Error::NotArticle(_)) => return None,
+        Err(_) => None,
+    };
+    let url = amp_url(html, input_url).filter(|url| !same_url(url, input_url))?;
+    let html = retrieve_html(&url, fetch_opts).await.ok()?;
+    let mut parsed = parse_from_html(&html, &url, parser_opts).await.ok()?;
+    if let Some(main) = main {
+        if parsed.quality.confidence <= main.quality.confidence {
+            return None;
+        }
+        parsed.url = main.url.clone();
+        parsed.links = main.links.clone();
+        parsed.url_choice = main.url_choice.clone();
+    }
+    parsed.amp_url = Some(url.clone());
+    Some(AmpPage { url, html, parsed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_amp_links() {
+        let html = fs::read_to_string("./test-data/amp-article.html").unwrap();
+        assert!(is_amp_document(&html));
+        assert!(!is_amp_document("<html><body></body></html>"));
+        assert_eq!(
+            amp_url(r#"<link rel="amphtml" href="/amp/story">"#, "https://somewhere.com/story").as_deref(),
+            Some("https://somewhere.com/amp/story")
+        );
+    }
+
+    #[test]
+    fn test_normalize_amp() {
+        let html = r#"<html amp><body>
+            <amp-img src="/a.jpg" width="800" height="450" alt="A &quot;quoted&quot; photo" layout="responsive">
+                <amp-img fallback src="/a-low.jpg"></amp-img></amp-img>
+            <amp-video src="/clip.mp4" poster="/clip.jpg" width="640" height="360">
+                <source src="/clip.webm" type="video/webm"><div fallback>No video</div></amp-video>
+            <amp-youtube data-videoid="dQw4w9WgXcQ" width="480" height="270" layout="responsive"></amp-youtube>
+            <amp-iframe src="https://codepen.io/embed/x" width="600" height="400" sandbox="allow-scripts"></amp-iframe>
+            <amp-carousel type="slides"><amp-img src="/1.jpg"></amp-img><amp-img src="/2.jpg"></amp-img></amp-carousel>
+            <amp-ad type="doubleclick" data-slot="/1/ad"><div placeholder>Ad</div></amp-ad>
+            <p>Fish &amp; chips</p>
+        </body></html>"#;
+        let result = normalize_amp(html);
+        assert!(result.contains(r#"<img src="/a.jpg" alt="A &quot;quoted&quot; photo" width="800" height="450">"#));
+        assert!(!result.contains("a-low.jpg"));
+        assert!(result.contains(
+            r#"<video controls src="/clip.mp4" poster="/clip.jpg" width="640" height="360"><source src="/clip.webm" type="video/webm"></video>"#
+        ));
+        assert!(!result.contains("No video"));
+        assert!(result.contains(r#"<iframe src="https://www.youtube.com/embed/dQw4w9WgXcQ" width="480" height="270""#));
+        assert!(result.contains(r#"<iframe src="https://codepen.io/embed/x" width="600" height="400"></iframe>"#));
+        assert!(result.contains(r#"<div><img src="/1.jpg"><img src="/2.jpg"></div>"#));
+        assert!(!result.contains("amp-") && !result.contains(">Ad<"));
+        assert!(result.contains("<p>Fish &amp; chips</p>"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_amp_article() {
+        let html = fs::read_to_string("./test-data/amp-article.html").unwrap();
+        let result = parse_from_html(&html, "https://somewhere.com/amp/story", &ParseOptions::default())
+            .await
+            .unwrap();
+        // The canonical of the AMP page wins over the AMP URL
+        assert_eq!(result.url, "https://somewhere.com/story");
+        assert!(result.content.contains("https://somewhere.com/images/bridge.jpg"));
+        assert!(result.content.contains("https://www.youtube.com/embed/dQw4w9WgXcQ"));
+        assert!(result.images.iter().any(|image| image.url == "https://somewhere.com/images/bridge.jpg"));
+    }
+}
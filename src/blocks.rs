@@ -0,0 +1,922 @@
+use crate::linker::get_domain;
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Html, Node};
+use serde::Serialize;
+
+/// Inline content of a block
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Inline {
+    Text { text: String },
+    /// `em`/`i`, or `strong`/`b` when `strong` is set
+    Emphasis { strong: bool, children: Vec<Inline> },
+    Link { href: String, children: Vec<Inline> },
+    Code { text: String },
+    Break,
+}
+
+/// A row of a `Block::Table`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TableRow {
+    /// Whether the row is made of `th` cells or sits in `thead`
+    pub header: bool,
+    pub cells: Vec<Vec<Inline>>,
+}
+
+/// A block of the extracted content.
+///
+/// `parse_blocks` builds the tree from the content HTML; `to_html`, `to_markdown`
+/// and `to_text` render it back.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Paragraph { spans: Vec<Inline> },
+    Heading { level: u8, id: Option<String>, spans: Vec<Inline> },
+    /// Every item is a list of blocks, so items may nest lists
+    List { ordered: bool, items: Vec<Vec<Block>> },
+    Quote { blocks: Vec<Block> },
+    Code { language: Option<String>, text: String },
+    Image {
+        src: String,
+        alt: String,
+        caption: Option<String>,
+        width: Option<u32>,
+        height: Option<u32>,
+    },
+    /// An `iframe`, `video` or `audio` player; `provider` is `video` or `audio`
+    /// for the latter two
+    Embed { provider: String, url: String },
+    Table { caption: Option<String>, rows: Vec<TableRow> },
+    Divider,
+}
+
+// Elements whose children are walked as if they were at the top level
+const CONTAINERS: &[&str] = &[
+    "div", "section", "article", "main", "header", "footer", "aside", "details", "summary",
+    "fieldset", "dl", "dd", "dt", "picture", "body", "html", "nav", "tbody", "center",
+];
+
+const IGNORED: &[&str] = &["script", "style", "noscript", "template", "svg", "form", "button"];
+
+fn normalize_whitespace(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space {
+                normalized.push(' ');
+            }
+            space = false;
+            normalized.push(c);
+        }
+    }
+    if space {
+        normalized.push(' ');
+    }
+    normalized
+}
+
+fn push_text(spans: &mut Vec<Inline>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(Inline::Text { text: last }) = spans.last_mut() {
+        // Collapse the space between adjacent text nodes
+        if last.ends_with(' ') && text.starts_with(' ') {
+            last.push_str(&text[1..]);
+        } else {
+            last.push_str(text);
+        }
+        return;
+    }
+    spans.push(Inline::Text { text: text.to_string() });
+}
+
+// Trim the whitespace at both ends of a run of spans, dropping empty text spans
+fn trim_spans(mut spans: Vec<Inline>) -> Vec<Inline> {
+    if let Some(Inline::Text { text }) = spans.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some(Inline::Text { text }) = spans.last_mut() {
+        *text = text.trim_end().to_string();
+    }
+    spans.retain(|span| !matches!(span, Inline::Text { text } if text.is_empty()));
+    while matches!(spans.last(), Some(Inline::Break)) {
+        spans.pop();
+    }
+    spans
+}
+
+fn spans_text(spans: &[Inline]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Inline::Text { text } | Inline::Code { text } => text.clone(),
+            Inline::Emphasis { children, .. } | Inline::Link { children, .. } => {
+                spans_text(children)
+            }
+            Inline::Break => "\n".to_string(),
+        })
+        .collect()
+}
+
+fn parse_dimension(element: &ElementRef, name: &str) -> Option<u32> {
+    element
+        .attr(name)?
+        .trim()
+        .trim_end_matches("px")
+        .parse()
+        .ok()
+}
+
+fn image_block(element: &ElementRef, caption: Option<String>) -> Option<Block> {
+    let src = element
+        .attr("src")
+        .or_else(|| element.attr("data-src"))?
+        .trim()
+        .to_string();
+    if src.is_empty() {
+        return None;
+    }
+    Some(Block::Image {
+        src,
+        alt: element.attr("alt").unwrap_or("").trim().to_string(),
+        caption,
+        width: parse_dimension(element, "width"),
+        height: parse_dimension(element, "height"),
+    })
+}
+
+/// Short name of the service hosting an embed, e.g. `youtube` or `vimeo`
+pub fn embed_provider(url: &str) -> String {
+    let host = get_domain(url).unwrap_or_default();
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+    match host {
+        "youtu.be" | "youtube.com" | "youtube-nocookie.com" => "youtube".to_string(),
+        "x.com" | "twitter.com" | "platform.twitter.com" => "twitter".to_string(),
+        "player.vimeo.com" | "vimeo.com" => "vimeo".to_string(),
+        "w.soundcloud.com" | "soundcloud.com" => "soundcloud".to_string(),
+        host => host.split('.').rev().nth(1).unwrap_or(host).to_string(),
+    }
+}
+
+fn embed_block(element: &ElementRef) -> Option<Block> {
+    let src = element.attr("src").map(str::to_string).or_else(|| {
+        element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|c| c.value().name() == "source")
+            .find_map(|c| c.attr("src").map(str::to_string))
+    })?;
+    let url = src.trim().to_string();
+    if url.is_empty() {
+        return None;
+    }
+    let provider = match element.value().name() {
+        "iframe" => embed_provider(&url),
+        name => name.to_string(),
+    };
+    Some(Block::Embed { provider, url })
+}
+
+fn code_language(element: &ElementRef) -> Option<String> {
+    let code = element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .find(|c| c.value().name() == "code");
+    [Some(*element), code]
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.attr("class"))
+        .flat_map(str::split_whitespace)
+        .find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+        })
+        .map(str::to_string)
+}
+
+fn element_caption(element: &ElementRef) -> Option<String> {
+    let text = normalize_whitespace(&element.text().collect::<String>());
+    Some(text.trim().to_string()).filter(|c| !c.is_empty())
+}
+
+// `caption` is the `figcaption` of an enclosing figure, if any
+fn table_block(element: &ElementRef, caption: Option<String>) -> Block {
+    let caption = caption.or_else(|| {
+        element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .find(|c| c.value().name() == "caption")
+            .and_then(|c| element_caption(&c))
+    });
+    let mut rows = Vec::new();
+    for row in element.descendants().filter_map(ElementRef::wrap) {
+        if row.value().name() != "tr" {
+            continue;
+        }
+        let in_head = row
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .any(|a| a.value().name() == "thead");
+        let cells: Vec<ElementRef> = row
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|c| matches!(c.value().name(), "td" | "th"))
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+        let header = in_head || cells.iter().all(|c| c.value().name() == "th");
+        let cells = cells
+            .iter()
+            .map(|cell| {
+                let mut images = Vec::new();
+                trim_spans(inline_children(*cell, &mut images))
+            })
+            .collect();
+        rows.push(TableRow { header, cells });
+    }
+    Block::Table { caption, rows }
+}
+
+fn inline_children(node: ElementRef, images: &mut Vec<Block>) -> Vec<Inline> {
+    let mut spans = Vec::new();
+    for child in node.children() {
+        collect_inline(child, &mut spans, images);
+    }
+    spans
+}
+
+// Inline content of a node; images met along the way are moved out as blocks
+fn collect_inline(node: NodeRef<Node>, spans: &mut Vec<Inline>, images: &mut Vec<Block>) {
+    match node.value() {
+        Node::Text(text) => push_text(spans, &normalize_whitespace(text)),
+        Node::Element(_) => {
+            let Some(element) = ElementRef::wrap(node) else {
+                return;
+            };
+            let name = element.value().name();
+            match name {
+                "br" => spans.push(Inline::Break),
+                "img" => images.extend(image_block(&element, None)),
+                "em" | "i" | "strong" | "b" => {
+                    let children = inline_children(element, images);
+                    if !spans_text(&children).trim().is_empty() {
+                        spans.push(Inline::Emphasis {
+                            strong: matches!(name, "strong" | "b"),
+                            children,
+                        });
+                    } else {
+                        spans.extend(children);
+                    }
+                }
+                "a" => {
+                    let children = inline_children(element, images);
+                    match element.attr("href") {
+                        Some(href) if !href.trim().is_empty() => spans.push(Inline::Link {
+                            href: href.trim().to_string(),
+                            children,
+                        }),
+                        _ => spans.extend(children),
+                    }
+                }
+                "code" | "kbd" | "samp" => spans.push(Inline::Code {
+                    text: element.text().collect(),
+                }),
+                name if IGNORED.contains(&name) => {}
+                _ => {
+                    for child in element.children() {
+                        collect_inline(child, spans, images);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_block(name: &str) -> bool {
+    CONTAINERS.contains(&name)
+        || matches!(
+            name,
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "li" | "blockquote"
+                | "pre" | "figure" | "table" | "hr" | "iframe" | "video" | "audio"
+        )
+}
+
+struct Builder {
+    blocks: Vec<Block>,
+    spans: Vec<Inline>,
+    images: Vec<Block>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            spans: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    // Close the pending run of inline content as a paragraph
+    fn flush(&mut self) {
+        let spans = trim_spans(std::mem::take(&mut self.spans));
+        if !spans.is_empty() {
+            self.blocks.push(Block::Paragraph { spans });
+        }
+        self.blocks.append(&mut self.images);
+    }
+
+    fn finish(mut self) -> Vec<Block> {
+        self.flush();
+        self.blocks
+    }
+
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            self.node(child);
+        }
+    }
+
+    fn node(&mut self, node: NodeRef<Node>) {
+        let Some(element) = ElementRef::wrap(node) else {
+            collect_inline(node, &mut self.spans, &mut self.images);
+            return;
+        };
+        let name = element.value().name();
+        if IGNORED.contains(&name) {
+            return;
+        }
+        if !is_block(name) {
+            collect_inline(node, &mut self.spans, &mut self.images);
+            return;
+        }
+        self.flush();
+        match name {
+            "p" => {
+                let spans = trim_spans(inline_children(element, &mut self.images));
+                if !spans.is_empty() {
+                    self.blocks.push(Block::Paragraph { spans });
+                }
+                self.blocks.append(&mut self.images);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let spans = trim_spans(inline_children(element, &mut self.images));
+                if !spans.is_empty() {
+                    self.blocks.push(Block::Heading {
+                        level: name[1..].parse().unwrap_or(1),
+                        id: element.attr("id").map(str::to_string),
+                        spans,
+                    });
+                }
+                self.blocks.append(&mut self.images);
+            }
+            "ul" | "ol" => {
+                let items: Vec<Vec<Block>> = element
+                    .children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|c| c.value().name() == "li")
+                    .map(parse_element)
+                    .filter(|item| !item.is_empty())
+                    .collect();
+                if !items.is_empty() {
+                    self.blocks.push(Block::List {
+                        ordered: name == "ol",
+                        items,
+                    });
+                }
+            }
+            "blockquote" => {
+                let blocks = parse_element(element);
+                if !blocks.is_empty() {
+                    self.blocks.push(Block::Quote { blocks });
+                }
+            }
+            "pre" => {
+                let text: String = element.text().collect();
+                if !text.trim().is_empty() {
+                    self.blocks.push(Block::Code {
+                        language: code_language(&element),
+                        text: text.trim_matches('\n').to_string(),
+                    });
+                }
+            }
+            "figure" => self.figure(element),
+            "table" => self.blocks.push(table_block(&element, None)),
+            "hr" => self.blocks.push(Block::Divider),
+            "iframe" | "video" | "audio" => self.blocks.extend(embed_block(&element)),
+            _ => self.children(element),
+        }
+    }
+
+    // A figure holds one media element or a gallery of them; the caption goes to
+    // the last image or table
+    fn figure(&mut self, element: ElementRef) {
+        let is_media = |e: &ElementRef| {
+            matches!(e.value().name(), "img" | "iframe" | "video" | "audio" | "table")
+        };
+        let caption = element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .find(|e| e.value().name() == "figcaption")
+            .and_then(|e| element_caption(&e));
+        // Media nested in another one, like the fallback image of a video, is skipped
+        let media: Vec<ElementRef> = element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .filter(is_media)
+            .filter(|e| {
+                !e.ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .take_while(|a| a.id() != element.id())
+                    .any(|a| is_media(&a))
+            })
+            .collect();
+        if media.is_empty() {
+            for child in element.children() {
+                let is_caption = ElementRef::wrap(child).is_some_and(|e| e.value().name() == "figcaption");
+                if !is_caption {
+                    self.node(child);
+                }
+            }
+            return;
+        }
+        let captioned = media.iter().rposition(|e| matches!(e.value().name(), "img" | "table"));
+        for (i, media) in media.iter().enumerate() {
+            let caption = if Some(i) == captioned { caption.clone() } else { None };
+            match media.value().name() {
+                "img" => self.blocks.extend(image_block(media, caption)),
+                "table" => self.blocks.push(table_block(media, caption)),
+                _ => self.blocks.extend(embed_block(media)),
+            }
+        }
+    }
+}
+
+fn parse_element(element: ElementRef) -> Vec<Block> {
+    let mut builder = Builder::new();
+    builder.children(element);
+    builder.finish()
+}
+
+/// Build the block tree of an HTML fragment, such as `ParsedContent::content`
+pub fn parse_blocks(html: &str) -> Vec<Block> {
+    let fragment = Html::parse_fragment(html);
+    parse_element(fragment.root_element())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn spans_html(spans: &[Inline]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Inline::Text { text } => escape_html(text),
+            Inline::Emphasis { strong: true, children } => {
+                format!("<strong>{}</strong>", spans_html(children))
+            }
+            Inline::Emphasis { strong: false, children } => {
+                format!("<em>{}</em>", spans_html(children))
+            }
+            Inline::Link { href, children } => {
+                format!(r#"<a href="{}">{}</a>"#, escape_html(href), spans_html(children))
+            }
+            Inline::Code { text } => format!("<code>{}</code>", escape_html(text)),
+            Inline::Break => "<br>".to_string(),
+        })
+        .collect()
+}
+
+/// Render blocks as HTML
+pub fn to_html(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Paragraph { spans } => format!("<p>{}</p>", spans_html(spans)),
+            Block::Heading { level, id, spans } => match id {
+                Some(id) => format!(
+                    r#"<h{0} id="{1}">{2}</h{0}>"#,
+                    level,
+                    escape_html(id),
+                    spans_html(spans)
+                ),
+                None => format!("<h{0}>{1}</h{0}>", level, spans_html(spans)),
+            },
+            Block::List { ordered, items } => {
+                let tag = if *ordered { "ol" } else { "ul" };
+                let items: String = items
+                    .iter()
+                    .map(|item| format!("<li>{}</li>", to_html(item)))
+                    .collect();
+                format!("<{0}>{1}</{0}>", tag, items)
+            }
+            Block::Quote { blocks } => format!("<blockquote>{}</blockquote>", to_html(blocks)),
+            Block::Code { language, text } => match language {
+                Some(language) => format!(
+                    r#"<pre><code class="language-{}">{}</code></pre>"#,
+                    escape_html(language),
+                    escape_html(text)
+                ),
+                None => format!("<pre><code>{}</code></pre>", escape_html(text)),
+            },
+            Block::Image {
+                src,
+                alt,
+                caption,
+                width,
+                height,
+            } => {
+                let mut img = format!(r#"<img src="{}" alt="{}""#, escape_html(src), escape_html(alt));
+                if let Some(width) = width {
+                    img.push_str(&format!(r#" width="{}""#, width));
+                }
+                if let Some(height) = height {
+                    img.push_str(&format!(r#" height="{}""#, height));
+                }
+                img.push('>');
+                match caption {
+                    Some(caption) => format!(
+                        "<figure>{}<figcaption>{}</figcaption></figure>",
+                        img,
+                        escape_html(caption)
+                    ),
+                    None => img,
+                }
+            }
+            Block::Embed { provider, url } => match provider.as_str() {
+                tag @ ("video" | "audio") => {
+                    format!(r#"<{0} controls src="{1}"></{0}>"#, tag, escape_html(url))
+                }
+                _ => format!(r#"<iframe src="{}"></iframe>"#, escape_html(url)),
+            },
+            Block::Table { caption, rows } => {
+                let rows: String = rows
+                    .iter()
+                    .map(|row| {
+                        let tag = if row.header { "th" } else { "td" };
+                        let cells: String = row
+                            .cells
+                            .iter()
+                            .map(|cell| format!("<{0}>{1}</{0}>", tag, spans_html(cell)))
+                            .collect();
+                        format!("<tr>{}</tr>", cells)
+                    })
+                    .collect();
+                match caption {
+                    Some(caption) => format!(
+                        "<table><caption>{}</caption>{}</table>",
+                        escape_html(caption),
+                        rows
+                    ),
+                    None => format!("<table>{}</table>", rows),
+                }
+            }
+            Block::Divider => "<hr>".to_string(),
+        })
+        .collect()
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Link destinations end at a space or an unbalanced parenthesis
+fn markdown_url(url: &str) -> String {
+    url.replace(' ', "%20").replace('(', "\\(").replace(')', "\\)")
+}
+
+// A code span fenced by more backticks than the longest run in its text
+fn markdown_code(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{0} {1} {0}", fence, text)
+    } else {
+        format!("{0}{1}{0}", fence, text)
+    }
+}
+
+fn spans_markdown(spans: &[Inline]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Inline::Text { text } => escape_markdown(text),
+            Inline::Emphasis { strong: true, children } => {
+                format!("**{}**", spans_markdown(children))
+            }
+            Inline::Emphasis { strong: false, children } => {
+                format!("*{}*", spans_markdown(children))
+            }
+            Inline::Link { href, children } => {
+                format!("[{}]({})", spans_markdown(children), markdown_url(href))
+            }
+            Inline::Code { text } => markdown_code(text),
+            Inline::Break => "  \n".to_string(),
+        })
+        .collect()
+}
+
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn markdown_block(block: &Block) -> String {
+    match block {
+        Block::Paragraph { spans } => spans_markdown(spans),
+        Block::Heading { level, spans, .. } => {
+            format!("{} {}", "#".repeat(*level as usize), spans_markdown(spans))
+        }
+        Block::List { ordered, items } => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = if *ordered {
+                    format!("{}. ", i + 1)
+                } else {
+                    "- ".to_string()
+                };
+                let rest = " ".repeat(marker.len());
+                indent(&to_markdown(item), &marker, &rest)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Quote { blocks } => indent(&to_markdown(blocks), "> ", "> "),
+        Block::Code { language, text } => {
+            format!("```{}\n{}\n```", language.as_deref().unwrap_or(""), text)
+        }
+        Block::Image { src, alt, caption, .. } => match caption {
+            Some(caption) => format!(
+                "![{}]({})\n*{}*",
+                escape_markdown(alt),
+                markdown_url(src),
+                escape_markdown(caption)
+            ),
+            None => format!("![{}]({})", escape_markdown(alt), markdown_url(src)),
+        },
+        Block::Embed { provider, url } => format!("[{}]({})", provider, markdown_url(url)),
+        Block::Table { caption, rows } => {
+            let width = rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+            let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+            let cells = |row: &TableRow| {
+                (0..width)
+                    .map(|i| {
+                        row.cells
+                            .get(i)
+                            .map(|c| spans_markdown(c).replace('|', "\\|"))
+                            .unwrap_or_default()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            // Markdown tables need a header row; an empty one is used when there is none
+            let (header, body) = match rows.first() {
+                Some(first) if first.header => (cells(first), &rows[1..]),
+                _ => (vec![String::new(); width], &rows[..]),
+            };
+            let mut lines = vec![line(header), line(vec!["---".to_string(); width])];
+            lines.extend(body.iter().map(|row| line(cells(row))));
+            if let Some(caption) = caption {
+                lines.push(format!("\n*{}*", escape_markdown(caption)));
+            }
+            lines.join("\n")
+        }
+        Block::Divider => "---".to_string(),
+    }
+}
+
+/// Render blocks as Markdown
+pub fn to_markdown(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(markdown_block)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn text_block(block: &Block) -> String {
+    match block {
+        Block::Paragraph { spans } | Block::Heading { spans, .. } => spans_text(spans),
+        Block::List { ordered, items } => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = if *ordered {
+                    format!("{}. ", i + 1)
+                } else {
+                    "- ".to_string()
+                };
+                let rest = " ".repeat(marker.len());
+                indent(&to_text(item), &marker, &rest)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Quote { blocks } => indent(&to_text(blocks), "  ", "  "),
+        Block::Code { text, .. } => text.clone(),
+        Block::Image { caption, .. } => caption.clone().unwrap_or_default(),
+        Block::Embed { url, .. } => url.clone(),
+        Block::Table { caption, rows } => rows
+            .iter()
+            .map(|row| {
+                row.cells
+                    .iter()
+                    .map(|cell| spans_text(cell))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .chain(caption.clone())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Divider => String::new(),
+    }
+}
+
+/// Render blocks as plain text, one block per paragraph
+pub fn to_text(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(text_block)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"
+        <h2 id="intro">The <em>intro</em></h2>
+        <p>Read the <a href="https://some.where/doc">full   doc</a> with <strong>care</strong>.<br>Next line</p>
+        <div><ul><li>One</li><li><p>Two</p><ol><li>Nested</li></ol></li></ul></div>
+        <blockquote><p>Quoted</p></blockquote>
+        <pre><code class="language-rust">fn main() {}</code></pre>
+        <figure><img src="https://some.where/a.png" alt="A chart" width="640" height="480"><figcaption>Sales by year</figcaption></figure>
+        <iframe src="https://www.youtube.com/embed/xyz"></iframe>
+        <table><thead><tr><th>Year</th><th>Sales</th></tr></thead><tr><td>2023</td><td>10</td></tr></table>
+        <hr>
+        Loose text
+    "#;
+
+    #[test]
+    fn test_parse_blocks() {
+        let blocks = parse_blocks(CONTENT);
+        assert_eq!(blocks.len(), 10);
+
+        assert_eq!(
+            blocks[0],
+            Block::Heading {
+                level: 2,
+                id: Some("intro".to_string()),
+                spans: vec![
+                    Inline::Text { text: "The ".to_string() },
+                    Inline::Emphasis {
+                        strong: false,
+                        children: vec![Inline::Text { text: "intro".to_string() }]
+                    },
+                ],
+            }
+        );
+        let Block::Paragraph { spans } = &blocks[1] else {
+            panic!("expected a paragraph");
+        };
+        assert_eq!(
+            spans[1],
+            Inline::Link {
+                href: "https://some.where/doc".to_string(),
+                children: vec![Inline::Text { text: "full doc".to_string() }],
+            }
+        );
+        assert!(spans.contains(&Inline::Break));
+
+        let Block::List { ordered: false, items } = &blocks[2] else {
+            panic!("expected a list");
+        };
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[1][1], Block::List { ordered: true, .. }));
+
+        assert!(matches!(&blocks[3], Block::Quote { blocks } if blocks.len() == 1));
+        assert_eq!(
+            blocks[4],
+            Block::Code {
+                language: Some("rust".to_string()),
+                text: "fn main() {}".to_string()
+            }
+        );
+        assert_eq!(
+            blocks[5],
+            Block::Image {
+                src: "https://some.where/a.png".to_string(),
+                alt: "A chart".to_string(),
+                caption: Some("Sales by year".to_string()),
+                width: Some(640),
+                height: Some(480),
+            }
+        );
+        assert_eq!(
+            blocks[6],
+            Block::Embed {
+                provider: "youtube".to_string(),
+                url: "https://www.youtube.com/embed/xyz".to_string()
+            }
+        );
+        let Block::Table { rows, .. } = &blocks[7] else {
+            panic!("expected a table");
+        };
+        assert!(rows[0].header && !rows[1].header);
+        assert_eq!(blocks[8], Block::Divider);
+        assert!(matches!(&blocks[9], Block::Paragraph { spans } if spans_text(spans) == "Loose text"));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let markdown = to_markdown(&parse_blocks(CONTENT));
+        let expected = [
+            "## The *intro*",
+            "Read the [full doc](https://some.where/doc) with **care**.  \nNext line",
+            "- One\n- Two\n\n  1. Nested",
+            "> Quoted",
+            "```rust\nfn main() {}\n```",
+            "![A chart](https://some.where/a.png)\n*Sales by year*",
+            "[youtube](https://www.youtube.com/embed/xyz)",
+            "| Year | Sales |\n| --- | --- |\n| 2023 | 10 |",
+            "---",
+            "Loose text",
+        ];
+        assert_eq!(markdown, expected.join("\n\n"));
+    }
+
+    #[test]
+    fn test_render_html_and_text() {
+        let blocks = parse_blocks(r#"<p>A &amp; <i>B</i></p><ul><li>C</li></ul>"#);
+        assert_eq!(to_html(&blocks), "<p>A &amp; <em>B</em></p><ul><li><p>C</p></li></ul>");
+        assert_eq!(to_text(&blocks), "A & B\n\n- C");
+        assert_eq!(parse_blocks(&to_html(&blocks)), blocks);
+    }
+
+    #[test]
+    fn test_figures_and_media() {
+        let blocks = parse_blocks(
+            r#"<figure class="gallery"><img src="/1.jpg"><img src="/2.jpg"><figcaption>Two photos</figcaption></figure>
+            <figure><table><tr><td>1</td></tr></table><figcaption>Totals</figcaption></figure>
+            <video src="/clip.mp4"><img src="/poster.jpg"></video>"#,
+        );
+        assert_eq!(blocks.len(), 4);
+        assert!(matches!(&blocks[0], Block::Image { src, caption: None, .. } if src == "/1.jpg"));
+        assert!(matches!(&blocks[1], Block::Image { src, caption: Some(c), .. } if src == "/2.jpg" && c == "Two photos"));
+        assert!(matches!(&blocks[2], Block::Table { caption: Some(c), .. } if c == "Totals"));
+        assert_eq!(
+            to_html(&blocks[2..]),
+            r#"<table><caption>Totals</caption><tr><td>1</td></tr></table><video controls src="/clip.mp4"></video>"#
+        );
+        assert_eq!(parse_blocks(&to_html(&blocks)), blocks);
+
+        let blocks = parse_blocks(r#"<audio src="/a.mp3"></audio><iframe src="https://player.vimeo.com/video/1"></iframe>"#);
+        assert_eq!(
+            to_html(&blocks),
+            r#"<audio controls src="/a.mp3"></audio><iframe src="https://player.vimeo.com/video/1"></iframe>"#
+        );
+    }
+
+    #[test]
+    fn test_markdown_escaping() {
+        let blocks = parse_blocks(
+            r#"<p><a href="https://en.wikipedia.org/wiki/Rust_(language) x">Rust</a> and <code>a `b` c</code> or <code>`</code></p>"#,
+        );
+        assert_eq!(
+            to_markdown(&blocks),
+            r"[Rust](https://en.wikipedia.org/wiki/Rust_\(language\)%20x) and ``a `b` c`` or `` ` ``"
+        );
+    }
+}
@@ -0,0 +1,547 @@
+use crate::linker::{purify, registrable_domain};
+use crate::similarity::compare_two_strings;
+use serde::{Deserialize, Serialize};
+
+/// Which variants of a URL `canonicalize` folds together.
+///
+/// Scheme and host are always lowercased, IDN hosts converted to punycode,
+/// default ports dropped and dot segments resolved, as the URL is parsed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CanonicalOptions {
+    /// `www.example.com` becomes `example.com`
+    pub fold_www: bool,
+    /// `/story/` becomes `/story`, the root path is kept
+    pub fold_trailing_slash: bool,
+    /// Order query parameters by key, then value
+    pub sort_query: bool,
+    /// `m.`, `mobile.` and `amp.` hosts, `/amp` paths and `.amp.html` files
+    /// map back to the desktop page
+    pub fold_mobile: bool,
+    /// Treat `http` as `https`
+    pub fold_scheme: bool,
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> Self {
+        Self {
+            fold_www: true,
+            fold_trailing_slash: true,
+            sort_query: true,
+            fold_mobile: true,
+            fold_scheme: false,
+        }
+    }
+}
+
+const MOBILE_HOST_PREFIXES: [&str; 3] = ["m.", "mobile.", "amp."];
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Decode percent-escaped unreserved characters and uppercase the hex digits of
+/// the remaining escapes, so `%7e` and `~` or `%2f` and `%2F` compare equal
+pub fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = input.get(i + 1..i + 3)
+            && let Ok(value) = u8::from_str_radix(hex, 16)
+        {
+            if is_unreserved(value) {
+                output.push(value as char);
+            } else {
+                output.push('%');
+                output.push_str(&hex.to_uppercase());
+            }
+            i += 3;
+            continue;
+        }
+        // Input is valid UTF-8, copy the whole character
+        let c = input[i..].chars().next().unwrap_or_default();
+        output.push(c);
+        i += c.len_utf8();
+    }
+    output
+}
+
+// Path without its AMP marker, `None` when there is none
+fn desktop_path(path: &str) -> Option<String> {
+    let trimmed = path.trim_end_matches('/');
+    if let Some(stripped) = trimmed.strip_suffix("/amp") {
+        return Some(if stripped.is_empty() { "/".to_string() } else { stripped.to_string() });
+    }
+    if let Some(stripped) = path.strip_prefix("/amp/") {
+        return Some(format!("/{}", stripped));
+    }
+    for extension in [".amp.html", ".amp.htm", ".amp"] {
+        if let Some(stripped) = path.strip_suffix(extension) {
+            let extension = extension.trim_start_matches(".amp");
+            return Some(format!("{}{}", stripped, extension));
+        }
+    }
+    None
+}
+
+/// Canonical form of a URL, for use as a deduplication key.
+///
+/// Tracking parameters and fragment are removed with `linker::purify` first.
+/// The result is not guaranteed to be served by the site, e.g. when
+/// `fold_www` drops a `www.` the site requires.
+pub fn canonicalize(url: &str, options: &CanonicalOptions) -> Option<String> {
+    let purified = purify(url)?;
+    let mut parsed = url::Url::parse(&purified).ok()?;
+    if parsed.cannot_be_a_base() {
+        return Some(purified);
+    }
+
+    if options.fold_scheme && parsed.scheme() == "http" {
+        let _ = parsed.set_scheme("https");
+    }
+    if let Some(host) = parsed.host_str().map(str::to_string) {
+        let mut host = host.as_str();
+        if options.fold_mobile
+            && let Some(stripped) = MOBILE_HOST_PREFIXES.iter().find_map(|p| host.strip_prefix(p))
+            && stripped.contains('.')
+        {
+            host = stripped;
+        }
+        if options.fold_www
+            && let Some(stripped) = host.strip_prefix("www.")
+            && stripped.contains('.')
+        {
+            host = stripped;
+        }
+        let host = host.to_string();
+        let _ = parsed.set_host(Some(&host));
+    }
+
+    let mut path = normalize_percent_encoding(parsed.path());
+    if options.fold_mobile
+        && let Some(desktop) = desktop_path(&path)
+    {
+        path = desktop;
+    }
+    if options.fold_trailing_slash && path.len() > 1 {
+        path = path.trim_end_matches('/').to_string();
+        if path.is_empty() {
+            path = "/".to_string();
+        }
+    }
+    parsed.set_path(&path);
+
+    if let Some(query) = parsed.query() {
+        let mut pairs: Vec<String> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(normalize_percent_encoding)
+            // `?amp=1` and `?amp` mark the AMP rendition of the page
+            .filter(|pair| !(options.fold_mobile && matches!(pair.as_str(), "amp" | "amp=1" | "amp=true")))
+            .collect();
+        if options.sort_query {
+            pairs.sort();
+        }
+        if pairs.is_empty() {
+            parsed.set_query(None);
+        } else {
+            parsed.set_query(Some(&pairs.join("&")));
+        }
+    }
+    Some(parsed.to_string())
+}
+
+/// Whether two URLs share a canonical form with the default options
+pub fn same_url(a: &str, b: &str) -> bool {
+    let options = CanonicalOptions::default();
+    match (canonicalize(a, &options), canonicalize(b, &options)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Drop the URLs whose canonical form appeared earlier in the list
+pub fn dedupe_urls(urls: &[String]) -> Vec<String> {
+    let options = CanonicalOptions::default();
+    let mut keys = Vec::new();
+    let mut unique = Vec::new();
+    for url in urls {
+        let key = canonicalize(url, &options).unwrap_or_else(|| url.clone());
+        if !keys.contains(&key) {
+            keys.push(key);
+            unique.push(url.clone());
+        }
+    }
+    unique
+}
+
+/// Where a candidate URL of a page comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlSource {
+    /// `<link rel="canonical">`
+    Canonical,
+    /// `og:url` and friends
+    OgUrl,
+    /// The URL the page was requested with
+    Input,
+    /// `<link rel="amphtml">`
+    AmpHtml,
+    /// `<link rel="shortlink">`
+    Shortlink,
+}
+
+/// Why a candidate was chosen or rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlReason {
+    /// The highest priority candidate that passed every check
+    Preferred,
+    /// Two valid candidates disagreed and the title matched this one better
+    TitleSimilarity,
+    /// Every candidate was rejected, the input URL is kept
+    Fallback,
+    Invalid,
+    Shortlink,
+    Amp,
+    /// On another site than the page
+    CrossSite,
+    /// Points to the home page of an article
+    HomePage,
+    /// Points to an article with a different slug than the page
+    DifferentArticle,
+}
+
+/// The URL chosen for a page and the reasoning behind it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UrlChoice {
+    pub url: String,
+    pub source: UrlSource,
+    pub reason: UrlReason,
+    pub rejected: Vec<(String, UrlSource, UrlReason)>,
+}
+
+impl Default for UrlChoice {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            source: UrlSource::Input,
+            reason: UrlReason::Fallback,
+            rejected: Vec::new(),
+        }
+    }
+}
+
+/// Candidate URLs of a page, as found in its metadata
+#[derive(Clone, Debug, Default)]
+pub struct UrlCandidates<'a> {
+    pub canonical: &'a str,
+    pub og_url: &'a str,
+    pub input: &'a str,
+    pub amphtml: &'a str,
+    pub shortlink: &'a str,
+}
+
+/// Hosts of URL shorteners, never used as the URL of an article
+const SHORTENER_HOSTS: &[&str] = &[
+    "bit.ly", "buff.ly", "dlvr.it", "fb.me", "goo.gl", "is.gd", "lnkd.in", "ow.ly", "shorturl.at", "t.co", "t.ly",
+    "tinyurl.com", "trib.al", "wp.me", "amzn.to", "youtu.be", "rebrand.ly", "cutt.ly", "sw.re",
+];
+
+fn is_shortener(url: &url::Url) -> bool {
+    let host = url.host_str().unwrap_or("").trim_start_matches("www.");
+    // WordPress shortlinks: `/?p=123`
+    let wordpress = url.path() == "/" && url.query_pairs().any(|(k, _)| k == "p");
+    SHORTENER_HOSTS.contains(&host) || wordpress
+}
+
+/// Whether a URL looks like the AMP rendition of a page
+pub fn is_amp_url(url: &str) -> bool {
+    let Ok(parsed) = url::Url::parse(url) else {
+        return false;
+    };
+    parsed.host_str().is_some_and(|h| h.starts_with("amp."))
+        || desktop_path(parsed.path()).is_some()
+        || parsed.query_pairs().any(|(k, v)| k == "amp" && matches!(v.as_ref(), "" | "1" | "true"))
+}
+
+fn is_home(url: &url::Url) -> bool {
+    url.path().trim_matches('/').is_empty() && url.query().is_none()
+}
+
+// Last path segment as words, `""` when it does not look like a slug
+fn slug_words(url: &url::Url) -> String {
+    let segment = url
+        .path_segments()
+        .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+        .unwrap_or("");
+    let segment = segment.split('.').next().unwrap_or("");
+    if segment.matches(['-', '_']).count() < 2 {
+        return String::new();
+    }
+    segment.replace(['-', '_'], " ")
+}
+
+/// Pick the URL of a page among its candidates.
+///
+/// Candidates are considered in the order canonical, og:url, input URL, AMP URL
+/// and shortlink. Invalid URLs, shortlinks, AMP URLs, URLs on another site than
+/// the page, and canonical or og:url values pointing to the home page or to an
+/// article with another slug are rejected. When both the canonical and og:url
+/// pass but disagree, the one whose slug matches the title better wins.
+pub fn resolve_url(candidates: &UrlCandidates, title: &str) -> UrlChoice {
+    let sources = [
+        (candidates.canonical, UrlSource::Canonical),
+        (candidates.og_url, UrlSource::OgUrl),
+        (candidates.input, UrlSource::Input),
+        (candidates.amphtml, UrlSource::AmpHtml),
+        (candidates.shortlink, UrlSource::Shortlink),
+    ];
+    let parse = |url: &str| {
+        purify(url.trim())
+            .and_then(|u| url::Url::parse(&u).ok())
+            .filter(|u| matches!(u.scheme(), "http" | "https"))
+    };
+    // The page is where it was fetched from, or where its metadata says it is
+    let page = [candidates.input, candidates.og_url, candidates.canonical]
+        .into_iter()
+        .filter_map(parse)
+        .find(|u| !is_shortener(u));
+    let site = page.as_ref().and_then(|p| registrable_domain(p.as_str()));
+    let page_slug = page.as_ref().map(slug_words).unwrap_or_default();
+    let page_is_home = page.as_ref().is_some_and(is_home);
+
+    let mut accepted: Vec<(String, UrlSource)> = Vec::new();
+    let mut rejected = Vec::new();
+    for (raw, source) in sources {
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let Some(parsed) = parse(raw) else {
+            rejected.push((raw.to_string(), source, UrlReason::Invalid));
+            continue;
+        };
+        let url = parsed.to_string();
+        let meta = matches!(source, UrlSource::Canonical | UrlSource::OgUrl);
+        let slug = slug_words(&parsed);
+        let reason = if source == UrlSource::Shortlink || is_shortener(&parsed) {
+            Some(UrlReason::Shortlink)
+        } else if source == UrlSource::AmpHtml || is_amp_url(&url) {
+            Some(UrlReason::Amp)
+        } else if site.is_some() && registrable_domain(&url) != site {
+            Some(UrlReason::CrossSite)
+        } else if meta && is_home(&parsed) && !page_is_home {
+            Some(UrlReason::HomePage)
+        } else if meta
+            && !slug.is_empty()
+            && !page_slug.is_empty()
+            && compare_two_strings(&slug, &page_slug) < 0.5
+            && compare_two_strings(&slug, title) + 0.2 < compare_two_strings(&page_slug, title)
+        {
+            Some(UrlReason::DifferentArticle)
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => rejected.push((url, source, reason)),
+            None if accepted.iter().any(|(u, _)| same_url(u, &url)) => {}
+            None => accepted.push((url, source)),
+        }
+    }
+
+    let Some((url, source)) = accepted.first().cloned() else {
+        // An AMP URL maps back to its desktop page, a shortlink never does
+        let desktop = || {
+            let options = CanonicalOptions {
+                fold_www: false,
+                fold_trailing_slash: false,
+                sort_query: false,
+                fold_mobile: true,
+                fold_scheme: false,
+            };
+            rejected
+                .iter()
+                .filter(|(_, _, reason)| *reason == UrlReason::Amp)
+                .filter_map(|(amp, source, _)| canonicalize(amp, &options).map(|url| (url, *source)))
+                .find(|(url, _)| !is_amp_url(url) && url::Url::parse(url).is_ok_and(|u| !is_shortener(&u)))
+        };
+        // The page itself is no fallback when it is the AMP version
+        let (url, source) = match (page.filter(|p| !is_amp_url(p.as_str())), desktop()) {
+            (Some(page), _) => (page.to_string(), UrlSource::Input),
+            (None, Some(desktop)) => desktop,
+            (None, None) => (String::new(), UrlSource::Input),
+        };
+        return UrlChoice {
+            url,
+            source,
+            reason: UrlReason::Fallback,
+            rejected,
+        };
+    };
+
+    // The canonical and og:url disagree: trust the one matching the title
+    if let Some((other, other_source)) = accepted.get(1).cloned()
+        && source == UrlSource::Canonical
+        && other_source == UrlSource::OgUrl
+    {
+        let score = |u: &str| {
+            url::Url::parse(u)
+                .map(|u| compare_two_strings(&slug_words(&u), title))
+                .unwrap_or(0.0)
+        };
+        if score(&other) > score(&url) + 0.1 {
+            return UrlChoice {
+                url: other,
+                source: other_source,
+                reason: UrlReason::TitleSimilarity,
+                rejected,
+            };
+        }
+    }
+    UrlChoice {
+        url,
+        source,
+        reason: UrlReason::Preferred,
+        rejected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let options = CanonicalOptions::default();
+        let entries = vec![
+            ("HTTPS://WWW.Example.COM:443/a/./b/../story/?b=2&a=1&utm_source=x#top", "https://example.com/a/story?a=1&b=2"),
+            ("http://example.com:80/%7euser/%2fdocs%3a", "http://example.com/~user/%2Fdocs%3A"),
+            ("https://bücher.example/katalog", "https://xn--bcher-kva.example/katalog"),
+            ("https://m.example.com/news/story/", "https://example.com/news/story"),
+            ("https://amp.example.com/news/story/amp/", "https://example.com/news/story"),
+            ("https://example.com/amp/news/story", "https://example.com/news/story"),
+            ("https://example.com/news/story.amp.html?amp=1", "https://example.com/news/story.html"),
+            ("https://example.com/", "https://example.com/"),
+            ("https://m.co/", "https://m.co/"),
+        ];
+        for (url, expected) in entries {
+            assert_eq!(canonicalize(url, &options).as_deref(), Some(expected), "URL: {}", url);
+        }
+
+        let strict = CanonicalOptions {
+            fold_www: false,
+            fold_trailing_slash: false,
+            sort_query: false,
+            fold_mobile: false,
+            fold_scheme: false,
+        };
+        assert_eq!(
+            canonicalize("https://www.example.com/story/amp/?b=2&a=1", &strict).as_deref(),
+            Some("https://www.example.com/story/amp/?b=2&a=1")
+        );
+        assert_eq!(canonicalize("not a url", &options), None);
+    }
+
+    #[test]
+    fn test_dedupe_urls() {
+        let urls: Vec<String> = vec![
+            "https://www.example.com/news/story",
+            "https://m.example.com/news/story/",
+            "https://example.com/news/story/amp",
+            "https://example.com/news/story?utm_source=tw",
+            "https://example.com/news/other",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(dedupe_urls(&urls), vec![urls[0].clone(), urls[4].clone()]);
+        assert!(same_url(&urls[1], &urls[3]));
+        assert!(!same_url(&urls[0], &urls[4]));
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let title = "Markets close higher after rate decision";
+        let candidates = UrlCandidates {
+            canonical: "https://news.tld/2024/05/markets-close-higher-after-rate-decision",
+            og_url: "https://news.tld/markets-close-higher-after-rate-decision?utm_source=fb",
+            input: "https://m.news.tld/2024/05/markets-close-higher-after-rate-decision/amp",
+            amphtml: "https://news.tld/2024/05/markets-close-higher-after-rate-decision/amp",
+            shortlink: "https://sw.re/419283",
+        };
+        let choice = resolve_url(&candidates, title);
+        assert_eq!(choice.url, candidates.canonical);
+        assert_eq!(choice.source, UrlSource::Canonical);
+        assert_eq!(choice.reason, UrlReason::Preferred);
+        let reasons: Vec<UrlReason> = choice.rejected.iter().map(|r| r.2).collect();
+        assert_eq!(reasons, vec![UrlReason::Amp, UrlReason::Amp, UrlReason::Shortlink]);
+
+        // A canonical pointing to the home page or to another article loses
+        let home = UrlCandidates {
+            canonical: "https://news.tld/",
+            input: "https://news.tld/markets-close-higher-after-rate-decision",
+            ..Default::default()
+        };
+        let choice = resolve_url(&home, title);
+        assert_eq!(choice.source, UrlSource::Input);
+        assert_eq!(choice.rejected[0].2, UrlReason::HomePage);
+
+        let other = UrlCandidates {
+            canonical: "https://news.tld/weather-turns-cold-for-weekend",
+            input: "https://news.tld/markets-close-higher-after-rate-decision",
+            ..Default::default()
+        };
+        assert_eq!(resolve_url(&other, title).rejected[0].2, UrlReason::DifferentArticle);
+
+        let cross = UrlCandidates {
+            canonical: "https://aggregator.example/markets-close-higher-after-rate-decision",
+            og_url: "https://news.tld/markets-close-higher-after-rate-decision",
+            ..Default::default()
+        };
+        let choice = resolve_url(&cross, title);
+        assert_eq!(choice.source, UrlSource::OgUrl);
+        assert_eq!(choice.rejected[0].2, UrlReason::CrossSite);
+    }
+
+    #[test]
+    fn test_resolve_url_tiebreak_and_fallback() {
+        let title = "Markets close higher after rate decision";
+        let candidates = UrlCandidates {
+            canonical: "https://news.tld/story/48213",
+            og_url: "https://news.tld/markets-close-higher-after-rate-decision",
+            ..Default::default()
+        };
+        let choice = resolve_url(&candidates, title);
+        assert_eq!(choice.source, UrlSource::OgUrl);
+        assert_eq!(choice.reason, UrlReason::TitleSimilarity);
+
+        let only_short = UrlCandidates {
+            shortlink: "https://sw.re/419283",
+            ..Default::default()
+        };
+        let choice = resolve_url(&only_short, title);
+        assert_eq!(choice.reason, UrlReason::Fallback);
+        assert_eq!(choice.url, "");
+
+        // Requested through a shortlink, the AMP URL leads back to the page
+        let short_and_amp = UrlCandidates {
+            input: "https://sw.re/419283",
+            amphtml: "https://amp.news.tld/markets-close-higher/amp",
+            ..Default::default()
+        };
+        let choice = resolve_url(&short_and_amp, title);
+        assert_eq!(choice.url, "https://news.tld/markets-close-higher");
+        assert_eq!((choice.source, choice.reason), (UrlSource::AmpHtml, UrlReason::Fallback));
+
+        // Fetched as AMP with no canonical, the desktop page is the URL
+        let amp_input = UrlCandidates {
+            input: "https://news.tld/story/amp",
+            ..Default::default()
+        };
+        let choice = resolve_url(&amp_input, title);
+        assert_eq!(choice.url, "https://news.tld/story");
+        assert_eq!((choice.source, choice.reason), (UrlSource::Input, UrlReason::Fallback));
+        assert_eq!(choice.rejected[0].2, UrlReason::Amp);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::extract::schema_types;
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::fmt;
+
+/// Kind of page behind a URL
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageType {
+    Article,
+    /// Tag, category, search, archive and other lists of teasers
+    Listing,
+    Homepage,
+    Video,
+    Product,
+    /// Author, user or staff page
+    Profile,
+    #[default]
+    Other,
+}
+
+impl fmt::Display for PageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Article => "article",
+            Self::Listing => "listing",
+            Self::Homepage => "homepage",
+            Self::Video => "video",
+            Self::Product => "product",
+            Self::Profile => "profile",
+            Self::Other => "other",
+        })
+    }
+}
+
+const PAGE_TYPES: [PageType; 6] = [
+    PageType::Article,
+    PageType::Listing,
+    PageType::Homepage,
+    PageType::Video,
+    PageType::Product,
+    PageType::Profile,
+];
+
+/// Evidence below which a page is classified as `Other`
+const MIN_EVIDENCE: f64 = 2.0;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PageClassification {
+    pub page_type: PageType,
+    /// Share of the evidence backing `page_type`, from 0 to 1
+    pub score: f64,
+    /// Signals that fired, e.g. `og:type=article` or `teasers=12`
+    pub signals: Vec<String>,
+}
+
+lazy_static! {
+    static ref DATE_PATH: Regex = Regex::new(r"/(19|20)\d{2}[/-](0?[1-9]|1[0-2])([/-]|$)|/(19|20)\d{6}/").unwrap();
+    static ref SLUG: Regex = Regex::new(r"^[\w]+(-[\w]+){3,}(\.html?)?$").unwrap();
+    static ref LISTING_PATH: Regex =
+        Regex::new(r"(?i)^/(.*/)?(tags?|category|categories|topics?|section|archives?|search|latest|page/\d+)(/|$)").unwrap();
+    static ref PROFILE_PATH: Regex =
+        Regex::new(r"(?i)^/(.*/)?(author|authors|profile|profiles|users?|people|staff|contributors?)(/|$)").unwrap();
+    static ref VIDEO_PATH: Regex = Regex::new(r"(?i)^/(.*/)?(videos?|watch|tv)(/|$)").unwrap();
+    static ref PRODUCT_PATH: Regex = Regex::new(r"(?i)^/(.*/)?(products?|shop|item|dp|p)/").unwrap();
+    static ref SEARCH_QUERY: Regex = Regex::new(r"(^|&)(q|s|query|search)=").unwrap();
+}
+
+struct Evidence {
+    scores: [f64; 6],
+    signals: Vec<String>,
+}
+
+impl Evidence {
+    fn add(&mut self, page_type: PageType, weight: f64, signal: String) {
+        if let Some(i) = PAGE_TYPES.iter().position(|t| *t == page_type) {
+            self.scores[i] += weight;
+        }
+        if !self.signals.contains(&signal) {
+            self.signals.push(signal);
+        }
+    }
+}
+
+fn og_type(document: &Html) -> Option<String> {
+    let selector = Selector::parse(r#"meta[property="og:type"], meta[name="og:type"]"#).unwrap();
+    document
+        .select(&selector)
+        .find_map(|m| m.attr("content"))
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+}
+
+fn text_length(element: &ElementRef) -> usize {
+    element.text().map(|t| t.split_whitespace().map(str::len).sum::<usize>()).sum()
+}
+
+// Share of the body text found inside links
+fn link_density(document: &Html) -> Option<f64> {
+    let body = document.select(&Selector::parse("body").unwrap()).next()?;
+    let total = text_length(&body);
+    if total == 0 {
+        return None;
+    }
+    let links: usize = body
+        .select(&Selector::parse("a").unwrap())
+        .map(|a| text_length(&a))
+        .sum();
+    Some(links as f64 / total as f64)
+}
+
+// Cards made of a linked heading, the building block of listings
+fn teaser_count(document: &Html) -> usize {
+    let selector = Selector::parse("h2 a[href], h3 a[href], h4 a[href], a[href] h2, a[href] h3, a[href] h4").unwrap();
+    let mut targets: Vec<&str> = document
+        .select(&selector)
+        .filter_map(|e| {
+            e.attr("href").or_else(|| {
+                e.ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .find(|a| a.value().name() == "a")
+                    .and_then(|a| a.attr("href"))
+            })
+        })
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets.len()
+}
+
+// Paragraphs long enough to be body copy
+fn long_paragraphs(document: &Html) -> usize {
+    document
+        .select(&Selector::parse("p").unwrap())
+        .filter(|p| p.text().collect::<String>().split_whitespace().count() >= 30)
+        .count()
+}
+
+/// Classify a page from its metadata, URL shape and layout
+pub fn classify_page(html: &str, url: &str) -> PageClassification {
+    let document = Html::parse_document(html);
+    let mut evidence = Evidence {
+        scores: [0.0; 6],
+        signals: Vec::new(),
+    };
+
+    if let Some(og) = og_type(&document) {
+        let signal = format!("og:type={}", og);
+        match og.as_str() {
+            "article" | "news" | "blog" => evidence.add(PageType::Article, 3.0, signal),
+            t if t.starts_with("video") => evidence.add(PageType::Video, 3.0, signal),
+            "product" | "og:product" => evidence.add(PageType::Product, 3.0, signal),
+            "profile" => evidence.add(PageType::Profile, 3.0, signal),
+            // Many sites declare `website` on every page
+            "website" => evidence.add(PageType::Homepage, 0.5, signal),
+            _ => {}
+        }
+    }
+
+    for schema in schema_types(&document) {
+        let signal = format!("schema={}", schema);
+        match schema.as_str() {
+            s if s.contains("article") || s == "report" => evidence.add(PageType::Article, 3.0, signal),
+            "collectionpage" | "searchresultspage" | "realestatelisting" => {
+                evidence.add(PageType::Listing, 3.0, signal)
+            }
+            "profilepage" | "aboutpage" => evidence.add(PageType::Profile, 3.0, signal),
+            "product" => evidence.add(PageType::Product, 3.0, signal),
+            // Articles embed videos too
+            "videoobject" => evidence.add(PageType::Video, 1.5, signal),
+            _ => {}
+        }
+    }
+
+    if let Ok(parsed) = url::Url::parse(url) {
+        let path = parsed.path();
+        let last = path.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        if path.trim_matches('/').is_empty() && parsed.query().is_none() {
+            evidence.add(PageType::Homepage, 4.0, "url=root".to_string());
+        }
+        if DATE_PATH.is_match(path) {
+            evidence.add(PageType::Article, 2.0, "url=date".to_string());
+        }
+        if SLUG.is_match(last) {
+            evidence.add(PageType::Article, 1.5, "url=slug".to_string());
+        }
+        if LISTING_PATH.is_match(path) || parsed.query().is_some_and(|q| SEARCH_QUERY.is_match(q)) {
+            evidence.add(PageType::Listing, 2.5, "url=listing".to_string());
+        }
+        if PROFILE_PATH.is_match(path) {
+            evidence.add(PageType::Profile, 2.5, "url=profile".to_string());
+        }
+        if VIDEO_PATH.is_match(path) {
+            evidence.add(PageType::Video, 1.5, "url=video".to_string());
+        }
+        if PRODUCT_PATH.is_match(path) {
+            evidence.add(PageType::Product, 1.5, "url=product".to_string());
+        }
+    }
+
+    let paragraphs = long_paragraphs(&document);
+    if paragraphs >= 4 {
+        evidence.add(PageType::Article, 2.0, format!("paragraphs={}", paragraphs));
+    }
+    let teasers = teaser_count(&document);
+    if teasers >= 6 {
+        evidence.add(PageType::Listing, 2.5, format!("teasers={}", teasers));
+        evidence.add(PageType::Homepage, 1.0, format!("teasers={}", teasers));
+    }
+    if let Some(density) = link_density(&document) {
+        let signal = format!("link_density={:.2}", density);
+        if density > 0.5 {
+            evidence.add(PageType::Listing, 2.0, signal.clone());
+            evidence.add(PageType::Homepage, 1.0, signal);
+        } else if density < 0.2 && paragraphs > 0 {
+            evidence.add(PageType::Article, 1.0, signal);
+        }
+    }
+    let players = Selector::parse(r#"video, iframe[src*="youtube"], iframe[src*="vimeo"], iframe[src*="player"]"#).unwrap();
+    if paragraphs < 2 && document.select(&players).next().is_some() {
+        evidence.add(PageType::Video, 2.0, "player".to_string());
+    }
+
+    let total: f64 = evidence.scores.iter().sum();
+    let (best, score) = evidence
+        .scores
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, score)| (PAGE_TYPES[i], *score))
+        .unwrap_or_default();
+    let page_type = if score < MIN_EVIDENCE { PageType::Other } else { best };
+    PageClassification {
+        page_type,
+        score: if page_type == PageType::Other || total == 0.0 {
+            0.0
+        } else {
+            score / total
+        },
+        signals: evidence.signals,
+    }
+}
+
+impl PageClassification {
+    pub fn is_article(&self) -> bool {
+        self.page_type == PageType::Article
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn paragraph(n: usize) -> String {
+        format!("<p>{}</p>", "Paragraph text with plenty of words in it to read. ".repeat(n))
+    }
+
+    #[test]
+    fn test_article() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let result = classify_page(&html, "https://somewhere.com/2024/05/some-long-article-slug");
+        assert_eq!(result.page_type, PageType::Article);
+        assert!(result.score > 0.5);
+        assert!(result.signals.contains(&"url=date".to_string()));
+    }
+
+    #[test]
+    fn test_listing_and_homepage() {
+        let teasers: String = (0..10)
+            .map(|i| format!(r#"<article><h3><a href="/news/story-{}">Story {}</a></h3></article>"#, i, i))
+            .collect();
+        let html = format!("<html><body><main>{}</main></body></html>", teasers);
+
+        let tag = classify_page(&html, "https://somewhere.com/tag/rust");
+        assert_eq!(tag.page_type, PageType::Listing);
+        assert!(tag.signals.contains(&"teasers=10".to_string()));
+
+        let home = classify_page(&html, "https://somewhere.com/");
+        assert_eq!(home.page_type, PageType::Homepage);
+    }
+
+    #[test]
+    fn test_metadata_types() {
+        let video = format!(
+            r#"<html><head><meta property="og:type" content="video.other"></head>
+            <body><video src="/clip.mp4"></video>{}</body></html>"#,
+            paragraph(1)
+        );
+        assert_eq!(classify_page(&video, "https://somewhere.com/watch/123").page_type, PageType::Video);
+
+        let product = r#"<html><head><script type="application/ld+json">{"@type": "Product", "name": "Kettle"}</script></head>
+            <body><h1>Kettle</h1></body></html>"#;
+        assert_eq!(classify_page(product, "https://shop.somewhere.com/p/kettle").page_type, PageType::Product);
+
+        let profile = format!("<html><body><h1>Ada Lovelace</h1>{}</body></html>", paragraph(1));
+        assert_eq!(classify_page(&profile, "https://somewhere.com/author/ada").page_type, PageType::Profile);
+
+        assert_eq!(
+            classify_page("<html><body><p>Hi</p></body></html>", "https://somewhere.com/about-us").page_type,
+            PageType::Other
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use crate::extract::ld_json_objects;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// A user comment, threaded through `parent`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Comment {
+    /// Identifier from the markup, or a generated `comment-<n>`
+    pub id: String,
+    pub author: String,
+    pub published: String,
+    /// Plain text of the comment
+    pub body: String,
+    /// `id` of the comment this one replies to
+    pub parent: Option<String>,
+}
+
+const MICRODATA_COMMENT: &str = r#"[itemtype*="schema.org/Comment"]"#;
+const WORDPRESS_COMMENT: &str = "#comments li.comment, #comments article.comment, .comment-list li.comment";
+const DISQUS_COMMENT: &str = "#disqus_thread li.post, #disqus_thread .post";
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn element_text(element: &ElementRef) -> String {
+    normalize_text(&element.text().collect::<Vec<_>>().join(" "))
+}
+
+// Generated ids keep replies pointing at the right parent when the markup has none.
+// They skip the `reserved` ids of the markup and those already handed out.
+fn ensure_id(id: Option<&str>, comments: &[Comment], reserved: &HashSet<String>) -> String {
+    match id.map(str::trim).filter(|id| !id.is_empty()) {
+        Some(id) => id.to_string(),
+        None => (comments.len() + 1..)
+            .map(|n| format!("comment-{}", n))
+            .find(|id| !reserved.contains(id) && comments.iter().all(|c| &c.id != id))
+            .unwrap(),
+    }
+}
+
+fn json_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => normalize_text(s),
+        Some(Value::Object(map)) => json_text(map.get("name")),
+        Some(Value::Array(items)) => json_text(items.first()),
+        _ => String::new(),
+    }
+}
+
+fn is_comment(value: &Value) -> bool {
+    match value.get("@type") {
+        Some(Value::String(t)) => t == "Comment",
+        Some(Value::Array(types)) => types.iter().any(|t| t == "Comment"),
+        _ => false,
+    }
+}
+
+// Every `@id` and `identifier` of a JSON-LD object, at any depth
+fn json_ids(value: &Value, ids: &mut HashSet<String>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| json_ids(item, ids)),
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::String(id) if key == "@id" || key == "identifier" => {
+                        ids.insert(id.trim().to_string());
+                    }
+                    value => json_ids(value, ids),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_json_comments(
+    value: &Value,
+    parent: Option<&str>,
+    reserved: &HashSet<String>,
+    comments: &mut Vec<Comment>,
+) {
+    let items = match value {
+        Value::Array(items) => items.iter().collect(),
+        value => vec![value],
+    };
+    for item in items.into_iter().filter(|item| is_comment(item)) {
+        let id = ensure_id(
+            item.get("@id").or(item.get("identifier")).and_then(Value::as_str),
+            comments,
+            reserved,
+        );
+        comments.push(Comment {
+            id: id.clone(),
+            author: json_text(item.get("author")),
+            published: json_text(item.get("dateCreated").or(item.get("datePublished"))),
+            body: json_text(item.get("text")),
+            parent: parent.map(str::to_string),
+        });
+        if let Some(replies) = item.get("comment") {
+            collect_json_comments(replies, Some(&id), reserved, comments);
+        }
+    }
+}
+
+fn json_ld_comments(document: &Html) -> Vec<Comment> {
+    let objects = ld_json_objects(document);
+    let mut reserved = HashSet::new();
+    objects.iter().for_each(|object| json_ids(object, &mut reserved));
+    let mut comments = Vec::new();
+    for object in objects {
+        if is_comment(&object) {
+            collect_json_comments(&object, None, &reserved, &mut comments);
+        } else if let Some(replies) = object.get("comment") {
+            collect_json_comments(replies, None, &reserved, &mut comments);
+        }
+    }
+    comments
+}
+
+// First descendant that does not belong to a nested comment, trying the
+// comma-separated alternatives of `selectors` in order of preference
+fn own_descendant<'a>(
+    element: &ElementRef<'a>,
+    selectors: &str,
+    comment: &Selector,
+) -> Option<ElementRef<'a>> {
+    selectors.split(',').find_map(|selector| {
+        let selector = Selector::parse(selector.trim()).unwrap();
+        element.select(&selector).find(|found| {
+            found
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .take_while(|a| a.id() != element.id())
+                .all(|a| !comment.matches(&a))
+        })
+    })
+}
+
+fn own_text(element: &ElementRef, selector: &str, comment: &Selector) -> String {
+    own_descendant(element, selector, comment)
+        .map(|e| element_text(&e))
+        .unwrap_or_default()
+}
+
+fn own_date(element: &ElementRef, selector: &str, comment: &Selector) -> String {
+    own_descendant(element, selector, comment)
+        .map(|e| {
+            ["datetime", "content", "title"]
+                .iter()
+                .find_map(|attr| e.attr(attr).filter(|v| !v.trim().is_empty()))
+                .map(|v| v.trim().to_string())
+                .unwrap_or_else(|| element_text(&e))
+        })
+        .unwrap_or_default()
+}
+
+/// Selectors locating the parts of a comment inside its container
+struct CommentMarkup {
+    container: &'static str,
+    author: &'static str,
+    published: &'static str,
+    body: &'static str,
+}
+
+const MARKUPS: &[CommentMarkup] = &[
+    CommentMarkup {
+        container: MICRODATA_COMMENT,
+        author: r#"[itemprop="author"] [itemprop="name"], [itemprop="author"]"#,
+        published: r#"[itemprop="dateCreated"], [itemprop="datePublished"]"#,
+        body: r#"[itemprop="text"]"#,
+    },
+    CommentMarkup {
+        container: WORDPRESS_COMMENT,
+        author: ".comment-author .fn, .comment-author",
+        published: ".comment-metadata time, .comment-meta time, time, .comment-meta a",
+        body: ".comment-content, .comment-body p",
+    },
+    CommentMarkup {
+        container: DISQUS_COMMENT,
+        author: ".post-byline .author, .author",
+        published: ".post-meta time, a.time-ago",
+        body: ".post-message",
+    },
+];
+
+fn markup_comments(document: &Html, markup: &CommentMarkup) -> Vec<Comment> {
+    let container = Selector::parse(markup.container).unwrap();
+    let with_id = Selector::parse("[id]").unwrap();
+    let reserved: HashSet<String> = document
+        .select(&with_id)
+        .filter_map(|element| element.attr("id"))
+        .map(|id| id.trim().to_string())
+        .collect();
+    let mut comments: Vec<Comment> = Vec::new();
+    let mut ids = Vec::new();
+    for element in document.select(&container) {
+        let body = own_text(&element, markup.body, &container);
+        if body.is_empty() {
+            continue;
+        }
+        // The closest enclosing comment is the one replied to
+        let parent = element
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .find(|a| container.matches(a))
+            .and_then(|a| ids.iter().find(|(node, _)| *node == a.id()))
+            .map(|(_, id): &(_, String)| id.clone());
+        let id = ensure_id(element.attr("id"), &comments, &reserved);
+        ids.push((element.id(), id.clone()));
+        comments.push(Comment {
+            id,
+            author: own_text(&element, markup.author, &container),
+            published: own_date(&element, markup.published, &container),
+            body,
+            parent,
+        });
+    }
+    comments
+}
+
+/// Extract the user comments of a page.
+///
+/// JSON-LD `Comment` objects are preferred, then schema.org microdata, WordPress
+/// `#comments` lists and server-rendered Disqus threads. Comments come in document
+/// order; replies reference the `id` of their parent.
+pub fn extract_comments(html: &str) -> Vec<Comment> {
+    let document = Html::parse_document(html);
+    let comments = json_ld_comments(&document);
+    if !comments.is_empty() {
+        return comments;
+    }
+    MARKUPS
+        .iter()
+        .map(|markup| markup_comments(&document, markup))
+        .find(|comments| !comments.is_empty())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_ld_comments() {
+        let html = r##"<html><head><script type="application/ld+json">
+            {
+                "@type": "BlogPosting",
+                "headline": "Post",
+                "comment": [{
+                    "@type": "Comment",
+                    "@id": "#c1",
+                    "author": {"@type": "Person", "name": "Ada"},
+                    "dateCreated": "2024-01-02T10:00:00Z",
+                    "text": "First!",
+                    "comment": {
+                        "@type": "Comment",
+                        "author": "Bob",
+                        "text": "Second."
+                    }
+                }]
+            }
+            </script></head><body></body></html>"##;
+        let comments = extract_comments(html);
+
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].id, "#c1");
+        assert_eq!(comments[0].author, "Ada");
+        assert_eq!(comments[0].published, "2024-01-02T10:00:00Z");
+        assert_eq!(comments[0].body, "First!");
+        assert_eq!(comments[0].parent, None);
+        assert_eq!(comments[1].id, "comment-2");
+        assert_eq!(comments[1].parent.as_deref(), Some("#c1"));
+    }
+
+    #[test]
+    fn test_wordpress_comments() {
+        let html = r#"<html><body><article><p>Post body</p></article>
+            <div id="comments"><ol class="comment-list">
+                <li id="comment-11" class="comment">
+                    <article class="comment-body">
+                        <footer class="comment-meta">
+                            <div class="comment-author"><b class="fn">Ada</b> says:</div>
+                            <div class="comment-metadata"><time datetime="2024-01-02T10:00:00+00:00">January 2</time></div>
+                        </footer>
+                        <div class="comment-content"><p>Great   post.</p></div>
+                    </article>
+                    <ol class="children">
+                        <li id="comment-12" class="comment">
+                            <div class="comment-author"><b class="fn">Bob</b></div>
+                            <div class="comment-content"><p>Agreed.</p></div>
+                        </li>
+                    </ol>
+                </li>
+            </ol></div></body></html>"#;
+        let comments = extract_comments(html);
+
+        assert_eq!(
+            comments,
+            vec![
+                Comment {
+                    id: "comment-11".to_string(),
+                    author: "Ada".to_string(),
+                    published: "2024-01-02T10:00:00+00:00".to_string(),
+                    body: "Great post.".to_string(),
+                    parent: None,
+                },
+                Comment {
+                    id: "comment-12".to_string(),
+                    author: "Bob".to_string(),
+                    published: String::new(),
+                    body: "Agreed.".to_string(),
+                    parent: Some("comment-11".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_microdata_and_disqus_comments() {
+        let html = r#"<html><body>
+            <div itemscope itemtype="https://schema.org/Comment" id="m1">
+                <span itemprop="author" itemscope itemtype="https://schema.org/Person"><span itemprop="name">Cy</span></span>
+                <meta itemprop="dateCreated" content="2024-03-04">
+                <div itemprop="text">Microdata comment</div>
+            </div></body></html>"#;
+        let comments = extract_comments(html);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].author, "Cy");
+        assert_eq!(comments[0].published, "2024-03-04");
+
+        let html = r#"<html><body><div id="disqus_thread"><ul id="post-list">
+            <li class="post" id="post-1">
+                <div class="post-byline"><span class="author">Dee</span></div>
+                <div class="post-meta"><a class="time-ago" title="Monday, May 6, 2024">2 days ago</a></div>
+                <div class="post-message"><p>From Disqus</p></div>
+            </li></ul></div></body></html>"#;
+        let comments = extract_comments(html);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].id, "post-1");
+        assert_eq!(comments[0].author, "Dee");
+        assert_eq!(comments[0].published, "Monday, May 6, 2024");
+        assert_eq!(comments[0].body, "From Disqus");
+
+        assert!(extract_comments("<html><body><p>No comments</p></body></html>").is_empty());
+    }
+
+    #[test]
+    fn test_generated_ids_skip_markup_ids() {
+        let html = r#"<html><body><div id="comments"><ol class="comment-list">
+            <li class="comment"><div class="comment-content"><p>No id here.</p></div>
+                <ol class="children"><li class="comment"><div class="comment-content"><p>Reply.</p></div></li></ol>
+            </li>
+            <li id="comment-1" class="comment"><div class="comment-content"><p>Real id.</p></div></li>
+            <li class="comment"><div class="comment-content"><p>Another.</p></div></li>
+        </ol></div><p id="comment-4">Not a comment</p></body></html>"#;
+        let comments = extract_comments(html);
+
+        let ids: Vec<_> = comments.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["comment-2", "comment-3", "comment-1", "comment-5"]);
+        assert_eq!(comments[1].parent.as_deref(), Some("comment-2"));
+
+        let html = r#"<html><head><script type="application/ld+json">
+            {"@type": "Article", "comment": [
+                {"@type": "Comment", "text": "First"},
+                {"@type": "Comment", "@id": "comment-1", "text": "Second"}
+            ]}
+            </script></head><body></body></html>"#;
+        let ids: Vec<_> = extract_comments(html).into_iter().map(|c| c.id).collect();
+        assert_eq!(ids, ["comment-2", "comment-1"]);
+    }
+}
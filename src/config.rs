@@ -20,7 +20,7 @@ impl Default for SanitizeHtmlOptions {
                 "u", "b", "i", "em", "strong", "small", "sup", "sub",
                 "div", "span", "p", "article", "blockquote", "section",
                 "details", "summary", "pre", "code", "ul", "ol", "li", "dd", "dl",
-                "table", "th", "tr", "td", "thead", "tbody", "tfoot", "fieldset", "legend",
+                "table", "caption", "th", "tr", "td", "thead", "tbody", "tfoot", "fieldset", "legend",
                 "figure", "figcaption", "img", "picture", "video", "audio", "source",
                 "iframe", "progress", "br", "p", "hr", "label", "abbr", "a", "svg",
             ].iter().map(|&s| s.to_string()).collect(),
@@ -33,9 +33,11 @@ impl Default for SanitizeHtmlOptions {
                 map.insert("h5".to_string(), vec!["id".to_string()]);
                 map.insert("h6".to_string(), vec!["id".to_string()]);
                 map.insert("a".to_string(), vec!["href".to_string(), "target".to_string(), "title".to_string()]);
+                map.insert("th".to_string(), vec!["colspan".to_string(), "rowspan".to_string(), "scope".to_string()]);
+                map.insert("td".to_string(), vec!["colspan".to_string(), "rowspan".to_string()]);
                 map.insert("abbr".to_string(), vec!["title".to_string()]);
                 map.insert("progress".to_string(), vec!["value".to_string(), "max".to_string()]);
-                map.insert("img".to_string(), vec!["src".to_string(), "srcset".to_string(), "alt".to_string(), "title".to_string()]);
+                map.insert("img".to_string(), vec!["src".to_string(), "srcset".to_string(), "alt".to_string(), "title".to_string(), "width".to_string(), "height".to_string()]);
                 map.insert("picture".to_string(), vec!["media".to_string(), "srcset".to_string()]);
                 map.insert("video".to_string(), vec!["controls".to_string(), "width".to_string(), "height".to_string(), "autoplay".to_string(), "muted".to_string(), "loop".to_string(), "src".to_string()]);
                 map.insert("audio".to_string(), vec!["controls".to_string(), "width".to_string(), "height".to_string(), "autoplay".to_string(), "muted".to_string(), "loop".to_string(), "src".to_string()]);
@@ -0,0 +1,543 @@
+use crate::classify::{classify_page, PageType};
+use crate::error::{AppResult as Result, Error};
+use crate::psl::registrable_domain;
+use crate::linker::{absolutify, is_valid_url, purify};
+use crate::parse_from_html::{parse_from_html, ParseOptions, ParsedContent};
+use crate::retrieve::{decode_html, retrieve, retrieve_with_type, RetrieveOptions};
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Scope and politeness of a crawl
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrawlOptions {
+    /// Link hops followed from the seeds, seeds being at depth 0
+    pub max_depth: usize,
+    /// Pages fetched before the crawl stops
+    pub max_pages: usize,
+    /// Only follow links on the sites of the seeds, by registrable domain
+    pub same_site: bool,
+    /// Extra hosts to follow links to, subdomains included
+    pub allowed_hosts: Vec<String>,
+    /// When not empty, only URLs matching one of these regexes are followed
+    pub include: Vec<String>,
+    /// URLs matching one of these regexes are never followed
+    pub exclude: Vec<String>,
+    /// Minimum delay between two requests to the same host
+    pub delay_ms: u64,
+    /// Skip URLs disallowed for all user agents in robots.txt and honor its `Crawl-delay`
+    pub respect_robots: bool,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            max_pages: 100,
+            same_site: true,
+            allowed_hosts: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            delay_ms: 1000,
+            respect_robots: true,
+        }
+    }
+}
+
+/// A URL waiting in the frontier
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FrontierEntry {
+    pub url: String,
+    pub depth: usize,
+}
+
+/// Progress of a crawl, saved to resume it later
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CrawlState {
+    pub frontier: VecDeque<FrontierEntry>,
+    /// URLs fetched or queued, so none is queued twice
+    pub seen: HashSet<String>,
+    /// Hosts of the seeds, the crawl stays on these sites when `same_site` is set
+    pub seed_hosts: Vec<String>,
+    pub pages_fetched: usize,
+}
+
+impl CrawlState {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = std::fs::read_to_string(path).map_err(|e| Error::AppError(e.to_string()))?;
+        serde_json::from_str(&json).map_err(|e| Error::AppError(format!("Invalid crawl state: {}", e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string(self).map_err(|e| Error::AppError(e.to_string()))?;
+        std::fs::write(path, json).map_err(|e| Error::AppError(e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum PageOutcome {
+    Article(Box<ParsedContent>),
+    /// Fetched and mined for links, but no article could be extracted
+    NotArticle,
+    /// Classified as a listing, home page or another kind of page that is only
+    /// mined for links
+    Skipped(PageType),
+    /// Disallowed by robots.txt
+    Disallowed,
+    /// Served with a `Content-Type` other than HTML, like a PDF or an image
+    NotHtml(String),
+    Failed(Error),
+}
+
+#[derive(Debug)]
+pub struct CrawledPage {
+    pub url: String,
+    pub depth: usize,
+    /// Links added to the frontier from this page
+    pub links_queued: usize,
+    pub outcome: PageOutcome,
+}
+
+/// A `Disallow` or `Allow` path, compiled once when robots.txt is parsed
+#[derive(Clone, Debug)]
+struct RobotsRule {
+    rule: String,
+    regex: Regex,
+}
+
+impl PartialEq for RobotsRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.rule == other.rule
+    }
+}
+
+impl RobotsRule {
+    // Prefix match with `*` wildcards and a `$` end anchor
+    fn new(rule: &str) -> Option<Self> {
+        let (pattern, anchored) = match rule.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (rule, false),
+        };
+        let pattern = pattern.split('*').map(regex::escape).collect::<Vec<_>>().join(".*");
+        let pattern = format!("^{}{}", pattern, if anchored { "$" } else { "" });
+        Regex::new(&pattern).ok().map(|regex| Self {
+            rule: rule.to_string(),
+            regex,
+        })
+    }
+}
+
+/// `Disallow`/`Allow` rules of the `User-agent: *` group of a robots.txt
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RobotsRules {
+    allow: Vec<RobotsRule>,
+    disallow: Vec<RobotsRule>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    pub fn parse(robots_txt: &str) -> Self {
+        let mut rules = Self::default();
+        let mut in_group = false;
+        let mut group_has_rules = false;
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim());
+            match key.as_str() {
+                "user-agent" => {
+                    // Consecutive user-agent lines share a group
+                    if group_has_rules {
+                        in_group = false;
+                        group_has_rules = false;
+                    }
+                    in_group |= value == "*";
+                }
+                "disallow" | "allow" | "crawl-delay" => {
+                    group_has_rules = true;
+                    if !in_group {
+                        continue;
+                    }
+                    match key.as_str() {
+                        "disallow" if !value.is_empty() => rules.disallow.extend(RobotsRule::new(value)),
+                        "allow" if !value.is_empty() => rules.allow.extend(RobotsRule::new(value)),
+                        "crawl-delay" => {
+                            rules.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64)
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        rules
+    }
+
+    /// Whether a path (with its query) may be fetched; the longest matching rule wins
+    pub fn allows(&self, path: &str) -> bool {
+        let longest = |rules: &[RobotsRule]| {
+            rules
+                .iter()
+                .filter(|rule| rule.regex.is_match(path))
+                .map(|rule| rule.rule.len())
+                .max()
+        };
+        match (longest(&self.allow), longest(&self.disallow)) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(allow), Some(disallow)) => allow >= disallow,
+        }
+    }
+}
+
+/// Extensions of links that never lead to an HTML page
+const NON_HTML_EXTENSIONS: &[&str] = &[
+    "7z", "apk", "avi", "bmp", "css", "csv", "dmg", "doc", "docx", "eot", "epub", "exe", "flac", "gif", "gz",
+    "ico", "iso", "jpeg", "jpg", "js", "json", "m4a", "m4v", "mkv", "mov", "mp3", "mp4", "odt", "ogg", "otf",
+    "pdf", "png", "ppt", "pptx", "rar", "rss", "svg", "tar", "tgz", "tif", "tiff", "ttf", "wav", "webm", "webp",
+    "woff", "woff2", "xls", "xlsx", "xml", "zip",
+];
+
+const HTML_TYPES: &[&str] = &["text/html", "application/xhtml+xml"];
+
+// Whether the last path segment of a URL ends with a non-HTML extension
+fn is_non_html_link(url: &str) -> bool {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            let segment = u.path_segments()?.next_back()?.to_string();
+            let (_, extension) = segment.rsplit_once('.')?;
+            Some(NON_HTML_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        })
+        .unwrap_or(false)
+}
+
+fn is_html_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    HTML_TYPES.contains(&mime.as_str())
+}
+
+fn host(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_lowercase()))
+}
+
+fn on_host(host: &str, allowed: &str) -> bool {
+    let allowed = allowed.trim_start_matches("www.");
+    host == allowed || host.ends_with(&format!(".{}", allowed))
+}
+
+/// Links of a page, absolute and purified
+pub fn discover_links(html: &str, page_url: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("a[href]").unwrap();
+    let mut links: Vec<String> = Vec::new();
+    for anchor in document.select(&selector) {
+        let href = anchor.attr("href").unwrap_or("").trim();
+        if href.is_empty() || href.starts_with('#') || anchor.attr("rel").is_some_and(|r| r.contains("nofollow")) {
+            continue;
+        }
+        let url = absolutify(page_url, href);
+        if !is_valid_url(&url) {
+            continue;
+        }
+        let url = purify(&url).unwrap_or(url);
+        if !links.contains(&url) {
+            links.push(url);
+        }
+    }
+    links
+}
+
+/// A breadth-first crawler extracting the articles of a few sites
+pub struct Crawler {
+    options: CrawlOptions,
+    parse_options: ParseOptions,
+    fetch_options: Option<RetrieveOptions>,
+    state: CrawlState,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    robots: HashMap<String, RobotsRules>,
+    last_request: HashMap<String, Instant>,
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| Error::AppError(format!("Invalid URL pattern {}: {}", p, e))))
+        .collect()
+}
+
+impl Crawler {
+    pub fn new(
+        seeds: &[&str],
+        options: CrawlOptions,
+        parse_options: ParseOptions,
+        fetch_options: Option<RetrieveOptions>,
+    ) -> Result<Self> {
+        let mut state = CrawlState::default();
+        for seed in seeds {
+            if !is_valid_url(seed) {
+                return Err(Error::AppError(format!("Invalid seed URL {}", seed)));
+            }
+            let url = purify(seed).unwrap_or(seed.to_string());
+            if let Some(host) = host(&url)
+                && !state.seed_hosts.contains(&host)
+            {
+                state.seed_hosts.push(host);
+            }
+            if state.seen.insert(url.clone()) {
+                state.frontier.push_back(FrontierEntry { url, depth: 0 });
+            }
+        }
+        Self::resume(state, options, parse_options, fetch_options)
+    }
+
+    /// Continue a crawl from a saved state
+    pub fn resume(
+        state: CrawlState,
+        options: CrawlOptions,
+        parse_options: ParseOptions,
+        fetch_options: Option<RetrieveOptions>,
+    ) -> Result<Self> {
+        Ok(Self {
+            include: compile(&options.include)?,
+            exclude: compile(&options.exclude)?,
+            options,
+            parse_options,
+            fetch_options,
+            state,
+            robots: HashMap::new(),
+            last_request: HashMap::new(),
+        })
+    }
+
+    pub fn state(&self) -> &CrawlState {
+        &self.state
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state.frontier.is_empty() || self.state.pages_fetched >= self.options.max_pages
+    }
+
+    /// Whether a link is in the scope of the crawl
+    pub fn in_scope(&self, url: &str) -> bool {
+        let Some(host) = host(url) else {
+            return false;
+        };
+        if is_non_html_link(url) {
+            return false;
+        }
+        let site = registrable_domain(&host);
+        let seed_site = self
+            .state
+            .seed_hosts
+            .iter()
+            .any(|seed| on_host(&host, seed) || (site.is_some() && registrable_domain(seed) == site));
+        let allowed = self.options.allowed_hosts.iter().any(|allowed| on_host(&host, allowed));
+        if !allowed && (self.options.same_site && !seed_site) {
+            return false;
+        }
+        (self.include.is_empty() || self.include.iter().any(|r| r.is_match(url)))
+            && !self.exclude.iter().any(|r| r.is_match(url))
+    }
+
+    async fn robots_for(&mut self, url: &str) -> RobotsRules {
+        let Some(host) = host(url) else {
+            return RobotsRules::default();
+        };
+        if let Some(rules) = self.robots.get(&host) {
+            return rules.clone();
+        }
+        let rules = match retrieve(&absolutify(url, "/robots.txt"), self.fetch_options.clone()).await {
+            Ok(bytes) => RobotsRules::parse(&String::from_utf8_lossy(&bytes)),
+            Err(_) => RobotsRules::default(),
+        };
+        self.robots.insert(host, rules.clone());
+        rules
+    }
+
+    async fn wait_turn(&mut self, url: &str, crawl_delay: Option<Duration>) {
+        let Some(host) = host(url) else {
+            return;
+        };
+        let delay = Duration::from_millis(self.options.delay_ms).max(crawl_delay.unwrap_or_default());
+        if let Some(last) = self.last_request.get(&host) {
+            let elapsed = last.elapsed();
+            if elapsed < delay {
+                tokio::time::sleep(delay - elapsed).await;
+            }
+        }
+        self.last_request.insert(host, Instant::now());
+    }
+
+    /// Fetch the next page of the frontier, `None` once the crawl is over
+    pub async fn next_page(&mut self) -> Option<CrawledPage> {
+        if self.is_done() {
+            return None;
+        }
+        let FrontierEntry { url, depth } = self.state.frontier.pop_front()?;
+        let mut page = CrawledPage {
+            url: url.clone(),
+            depth,
+            links_queued: 0,
+            outcome: PageOutcome::NotArticle,
+        };
+
+        let mut crawl_delay = None;
+        if self.options.respect_robots {
+            let rules = self.robots_for(&url).await;
+            let path = url::Url::parse(&url)
+                .map(|u| match u.query() {
+                    Some(query) => format!("{}?{}", u.path(), query),
+                    None => u.path().to_string(),
+                })
+                .unwrap_or_default();
+            if !rules.allows(&path) {
+                page.outcome = PageOutcome::Disallowed;
+                return Some(page);
+            }
+            crawl_delay = rules.crawl_delay;
+        }
+
+        self.wait_turn(&url, crawl_delay).await;
+        self.state.pages_fetched += 1;
+        let bytes = match retrieve_with_type(&url, self.fetch_options.clone()).await {
+            // Without a `Content-Type` the markup decides
+            Ok((_, Some(content_type))) if !is_html_type(&content_type) => {
+                page.outcome = PageOutcome::NotHtml(content_type);
+                return Some(page);
+            }
+            Ok((bytes, _)) => bytes,
+            Err(err) => {
+                page.outcome = PageOutcome::Failed(err);
+                return Some(page);
+            }
+        };
+        let html = match decode_html(&bytes, &url) {
+            Ok(html) => html,
+            Err(err) => {
+                page.outcome = PageOutcome::Failed(err);
+                return Some(page);
+            }
+        };
+
+        if depth < self.options.max_depth {
+            for link in discover_links(&html, &url) {
+                if self.in_scope(&link) && self.state.seen.insert(link.clone()) {
+                    self.state.frontier.push_back(FrontierEntry { url: link, depth: depth + 1 });
+                    page.links_queued += 1;
+                }
+            }
+        }
+
+        // Listings and home pages are only mined for links
+        let classification = classify_page(&html, &url);
+        if !classification.is_article() {
+            page.outcome = PageOutcome::Skipped(classification.page_type);
+            return Some(page);
+        }
+        page.outcome = match parse_from_html(&html, &url, &self.parse_options).await {
+            Ok(parsed) => PageOutcome::Article(Box::new(parsed)),
+            Err(Error::NullError(_)) => PageOutcome::NotArticle,
+            Err(Error::NotArticle(page_type)) => PageOutcome::Skipped(page_type),
+            Err(err) => PageOutcome::Failed(err),
+        };
+        Some(page)
+    }
+
+    /// Crawl until the frontier is empty or `max_pages` is reached, returning the articles
+    pub async fn run(&mut self) -> Vec<ParsedContent> {
+        let mut articles = Vec::new();
+        while let Some(page) = self.next_page().await {
+            if let PageOutcome::Article(parsed) = page.outcome {
+                articles.push(*parsed);
+            }
+        }
+        articles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_robots_rules() {
+        let robots = "User-agent: Googlebot\nDisallow: /\n\nUser-agent: *\nDisallow: /admin\nDisallow: /*.pdf$\nAllow: /admin/public\nCrawl-delay: 2\n";
+        let rules = RobotsRules::parse(robots);
+        assert!(rules.allows("/news/story"));
+        assert!(!rules.allows("/admin/settings"));
+        assert!(rules.allows("/admin/public/page"));
+        assert!(!rules.allows("/files/report.pdf"));
+        assert!(rules.allows("/files/report.pdf?download=1"));
+        assert_eq!(rules.crawl_delay, Some(Duration::from_secs(2)));
+
+        assert!(RobotsRules::parse("").allows("/anything"));
+        assert_eq!(rules, RobotsRules::parse(robots));
+        assert_eq!(rules.disallow.len(), 2);
+    }
+
+    #[test]
+    fn test_html_types() {
+        assert!(is_html_type("text/html; charset=utf-8"));
+        assert!(is_html_type("Application/XHTML+XML"));
+        assert!(!is_html_type("application/pdf"));
+        assert!(!is_html_type("image/jpeg"));
+    }
+
+    #[test]
+    fn test_discover_links() {
+        let html = r##"<html><body>
+            <a href="/news/one?utm_source=x#top">One</a>
+            <a href="https://somewhere.com/news/one">One again</a>
+            <a href="#comments">Comments</a>
+            <a href="mailto:desk@somewhere.com">Mail</a>
+            <a href="/login" rel="nofollow">Login</a>
+            <a href="https://elsewhere.org/story">Elsewhere</a>
+        </body></html>"##;
+        assert_eq!(
+            discover_links(html, "https://somewhere.com/"),
+            vec!["https://somewhere.com/news/one", "https://elsewhere.org/story"]
+        );
+    }
+
+    #[test]
+    fn test_scope_and_state() {
+        let options = CrawlOptions {
+            allowed_hosts: vec!["partner.org".to_string()],
+            exclude: vec![r"/tag/".to_string()],
+            ..Default::default()
+        };
+        let crawler = Crawler::new(&["https://www.somewhere.com/"], options, ParseOptions::default(), None).unwrap();
+        assert!(crawler.in_scope("https://somewhere.com/news/one"));
+        assert!(crawler.in_scope("https://blog.somewhere.com/post"));
+        assert!(!crawler.in_scope("https://somewhere.github.io/post"));
+        assert!(crawler.in_scope("https://news.partner.org/story"));
+        assert!(!crawler.in_scope("https://elsewhere.org/story"));
+        assert!(!crawler.in_scope("https://somewhere.com/tag/rust"));
+        assert!(!crawler.in_scope("https://somewhere.com/files/report.PDF"));
+        assert!(!crawler.in_scope("https://somewhere.com/media/clip.mp4?autoplay=1"));
+        assert!(crawler.in_scope("https://somewhere.com/news/story.html"));
+        assert!(crawler.in_scope("https://somewhere.com/news/v2.5/story"));
+
+        let path = std::env::temp_dir().join("scrapyy-crawl-state.json");
+        crawler.state().save(&path).unwrap();
+        let state = CrawlState::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&state, crawler.state());
+        assert_eq!(state.frontier[0].url, "https://www.somewhere.com/");
+
+        let invalid = CrawlOptions {
+            include: vec!["(".to_string()],
+            ..Default::default()
+        };
+        assert!(Crawler::resume(state, invalid, ParseOptions::default(), None).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::classify::PageType;
+use crate::paywall::AccessStatus;
 use reqwest::StatusCode;
 
 #[derive(Debug, thiserror::Error)]
@@ -13,7 +15,11 @@ pub enum Error {
     #[error("{0}")]
     Html2TextError(#[from] html2text::Error),
     #[error("Unsupported encoding {0}")]
-    UnsupportedEncoding(String)
+    UnsupportedEncoding(String),
+    #[error("Content is behind a {0}")]
+    RestrictedContent(AccessStatus),
+    #[error("Page is a {0}, not an article")]
+    NotArticle(PageType),
 }
 
 pub type AppResult<T> = Result<T, Error>;
@@ -1,10 +1,11 @@
+use crate::linker::get_domain;
+use crate::psl::{registrable_domain, source_name};
+use crate::readability::{self, ReadabilityOptions};
 use lazy_static::lazy_static;
-use readability::extractor;
 use scraper::{Html, Selector, element_ref::ElementRef};
 use serde::Serialize;
 use serde_json::Value;
-use std::{collections::HashMap, io::Cursor};
-use url::Url;
+use std::collections::HashMap;
 
 macro_rules! create_setter {
     ($field:ident, $type:ty) => {
@@ -121,6 +122,8 @@ lazy_static! {
             "satiricalarticle",
             "scholarlyarticle",
             "medicalscholarlyarticle",
+            "product",
+            "videoobject",
         ]
         .iter()
         .map(|c| c.to_string())
@@ -137,25 +140,52 @@ const ATTRIBUTE_LISTS: &[(&str, &str)] = &[
     ("type", "@type"),
 ];
 
-/// Parses JSON-LD data from a document and populates an entry object.
-/// Only populates if the original entry object is empty or undefined.
-fn extract_ld_schema(document: &Html, _entry: &mut MetaEntry) {
-    // TODO: FIX ME - Complete JSON-LD parsing implementation
-    // let mut entry = entry;
-    if let Ok(selector) = Selector::parse(r#"script[type="application/ld+json"]"#) {
-        document.select(&selector).for_each(|element| {
-            if let Some(ldschema) = element.text().next() {
-                let _ld_json: Value =
-                    serde_json::from_str(ldschema).expect("Failed to parse JSON-LD");
-
-                //     let ld_map: HashMap<String, Value> = match ld_json {
-                //         Value::Object(map) => HashMap::from_iter(map),
-                //         _ => panic!("Expected a JSON object"),
-                //     };
-                //
-            };
-        });
+/// Collect every JSON-LD object of a document, flattening top-level arrays
+/// and `@graph` lists. Scripts that fail to parse are skipped.
+pub fn ld_json_objects(document: &Html) -> Vec<Value> {
+    let selector = Selector::parse(r#"script[type="application/ld+json"]"#).unwrap();
+    let mut objects = Vec::new();
+    for element in document.select(&selector) {
+        let text = element.text().collect::<String>();
+        let Ok(value) = serde_json::from_str::<Value>(text.trim()) else {
+            continue;
+        };
+        let mut pending = vec![value];
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::Array(items) => pending.extend(items.into_iter().rev()),
+                Value::Object(mut map) => {
+                    if let Some(graph) = map.remove("@graph") {
+                        pending.push(graph);
+                    }
+                    if !map.is_empty() {
+                        objects.push(Value::Object(map));
+                    }
+                }
+                _ => {}
+            }
+        }
     }
+    objects
+}
+
+/// Known page `@type`s of the JSON-LD objects, lowercased. `@type` may hold a list
+pub fn schema_types(document: &Html) -> Vec<String> {
+    let mut types = Vec::new();
+    for object in ld_json_objects(document) {
+        let names = match object.get("@type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+            _ => continue,
+        };
+        for name in names {
+            let name = name.to_lowercase();
+            if TYPE_SCHEMAS.contains(&name) && !types.contains(&name) {
+                types.push(name);
+            }
+        }
+    }
+    types
 }
 
 fn set_property(entry: &mut MetaEntry, field: &str, value: String) {
@@ -166,6 +196,11 @@ fn set_property(entry: &mut MetaEntry, field: &str, value: String) {
 
 // TODO: Verify and fix
 pub fn extract_metadata(html: &str) -> MetaEntry {
+    extract_metadata_from_document(&Html::parse_document(html))
+}
+
+// Same as `extract_metadata`, on a document that is already parsed
+pub fn extract_metadata_from_document(document: &Html) -> MetaEntry {
     let mut entry = MetaEntry::default();
 
     let attributes = HashMap::from([
@@ -240,8 +275,6 @@ pub fn extract_metadata(html: &str) -> MetaEntry {
         ("type", vec!["og:type"]),
     ]);
 
-    let document = Html::parse_document(html);
-
     if let Some(title) = document
         .select(&Selector::parse("head > title").unwrap())
         .next()
@@ -264,58 +297,73 @@ pub fn extract_metadata(html: &str) -> MetaEntry {
         }
     }
 
-    extract_ld_schema(&document, &mut entry);
     entry
 }
 
 // Function to extract content with readability
 pub fn extract_with_readability(html: &str, url: &str) -> Option<String> {
+    extract_with_readability_options(html, url, &ReadabilityOptions::default())
+}
+
+// Function to extract content with readability, using the given scoring options
+pub fn extract_with_readability_options(
+    html: &str,
+    url: &str,
+    options: &ReadabilityOptions,
+) -> Option<String> {
     if html.trim().is_empty() {
         return None;
     }
 
-    let mut document = Cursor::new(html.as_bytes());
-
-    // Handle invalid URLs gracefully - use a default URL for local HTML
-    let parsed_url = Url::parse(url)
-        .or_else(|_| Url::parse("https://example.com"))
-        .ok()?;
+    let document = Html::parse_document(html);
+    let article = readability::extract(&document, url, options)?;
 
-    match extractor::extract(&mut document, &parsed_url) {
-        Ok(result) => {
-            if result.content.is_empty() {
-                return None;
-            }
-            // Strip tags to check for meaningful text content
-            let text = html2text::from_read(result.content.as_bytes(), 80).ok()?;
-            if text.trim().len() < 10 {
-                return None;
-            }
-            Some(result.content)
-        }
-        Err(_) => None,
+    // Strip tags to check for meaningful text content
+    let text = html2text::from_read(article.content.as_bytes(), 80).ok()?;
+    if text.trim().len() < 10 {
+        return None;
     }
+    Some(article.content)
 }
 
 // Function to extract title with readability
 pub fn extract_title_with_readability(html: &str, url: &str) -> Option<String> {
-    let mut document = Cursor::new(html.as_bytes());
-
-    // Handle invalid URLs gracefully - use a default URL for local HTML
-    let parsed_url = Url::parse(url)
-        .or_else(|_| Url::parse("https://example.com"))
-        .ok()?;
-
-    match extractor::extract(&mut document, &parsed_url) {
-        Ok(result) => {
-            if !result.title.is_empty() {
-                Some(result.title)
-            } else {
-                None
-            }
+    extract_title_from_document(&Html::parse_document(html), url)
+}
+
+const TITLE_SEPARATORS: &[&str] = &[" | ", " - ", " – ", " — ", " :: ", " / ", " » ", " · "];
+
+// Whether a title segment names the site of `url`: "BBC News" is not "BBC"
+fn names_site(segment: &str, url: &str) -> bool {
+    let key = |s: &str| -> String {
+        s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+    };
+    let segment = key(segment);
+    let host = get_domain(url).unwrap_or_default();
+    !segment.is_empty()
+        && [source_name(url), registrable_domain(&host).unwrap_or_default(), host]
+            .iter()
+            .any(|name| key(name) == segment)
+}
+
+// Title of a parsed document, without the site name the page appends or prepends
+pub fn extract_title_from_document(document: &Html, url: &str) -> Option<String> {
+    let title = readability::get_title(document)?;
+    for separator in TITLE_SEPARATORS {
+        if let Some((head, tail)) = title.rsplit_once(separator)
+            && !head.trim().is_empty()
+            && names_site(tail, url)
+        {
+            return Some(head.trim().to_string());
+        }
+        if let Some((head, tail)) = title.split_once(separator)
+            && !tail.trim().is_empty()
+            && names_site(head, url)
+        {
+            return Some(tail.trim().to_string());
         }
-        Err(_) => None,
     }
+    Some(title)
 }
 
 #[cfg(test)]
@@ -481,6 +529,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_extract_metadata_malformed_ld_json() {
+        let html = r#"<html><head><title>Broken</title>
+            <script type="application/ld+json">{"@type": "NewsArticle", "headline": </script>
+            </head><body></body></html>"#;
+        assert_eq!(extract_metadata(html).title, "Broken");
+    }
+
     #[test]
     fn test_extract_ld_schema() {
         let html = r#"
@@ -536,6 +592,21 @@ mod tests {
         assert_eq!(result.unwrap(), "Article title here - ArticleParser");
     }
 
+    #[test]
+    fn test_extract_title_site_name() {
+        let html = read_file("./test-data/regular-article.html");
+        let document = Html::parse_document(&html);
+        assert_eq!(
+            extract_title_from_document(&document, "https://articleparser.com/story").as_deref(),
+            Some("Article title here")
+        );
+        // A segment that does not name the site is part of the title
+        assert_eq!(
+            extract_title_from_document(&document, "https://somewhere.com/story").as_deref(),
+            Some("Article title here - ArticleParser")
+        );
+    }
+
     #[test]
     fn test_extract_title_from_page_without_title() {
         let html = read_file("./test-data/html-no-title.html");
@@ -543,4 +614,20 @@ mod tests {
         let result = extract_title_with_readability(&html, url);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_ld_json_objects() {
+        let html = r#"
+            <script type="application/ld+json">[{"@type": "Organization"}, {"@graph": [{"@type": "NewsArticle"}, {"@type": "WebPage"}]}]</script>
+            <script type="application/ld+json">{ not json }</script>
+            <script type="application/ld+json">{"@type": "Person"}</script>
+        "#;
+        let objects = ld_json_objects(&Html::parse_document(html));
+        let types: Vec<&str> = objects.iter().filter_map(|o| o["@type"].as_str()).collect();
+        assert_eq!(types, vec!["Organization", "NewsArticle", "WebPage", "Person"]);
+        assert_eq!(
+            schema_types(&Html::parse_document(html)),
+            vec!["newsarticle", "webpage"]
+        );
+    }
 }
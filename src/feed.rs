@@ -0,0 +1,466 @@
+use crate::error::{AppResult as Result, Error};
+use crate::linker::absolutify;
+use crate::parse_from_html::{ParseOptions, ParsedContent};
+use crate::retrieve::{retrieve_with_type, RetrieveOptions};
+use encoding_rs::{Encoding, UTF_8};
+use lazy_static::lazy_static;
+use regex::Regex;
+use roxmltree::{Document, Node, ParsingOptions};
+use scraper::{Html, Selector};
+use serde::Serialize;
+
+/// Paths tried when a page advertises no feed
+const COMMON_FEED_PATHS: [&str; 6] = ["/feed", "/rss.xml", "/atom.xml", "/feed.xml", "/index.xml", "/rss"];
+
+lazy_static! {
+    static ref XML_ENCODING: Regex =
+        Regex::new(r#"^\s*<\?xml[^>]*?\sencoding\s*=\s*["']([A-Za-z0-9._:-]+)["']"#).unwrap();
+    static ref CONTENT_TYPE_CHARSET: Regex = Regex::new(r#"(?i)charset\s*=\s*"?([A-Za-z0-9._:-]+)"#).unwrap();
+}
+
+const FEED_TYPES: [&str; 4] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/rdf+xml",
+    "application/feed+json",
+];
+
+/// A feed advertised by a page
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FeedLink {
+    pub url: String,
+    pub title: Option<String>,
+    pub mime_type: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    /// RSS 0.9x and 2.0
+    #[default]
+    Rss,
+    /// RSS 1.0
+    Rdf,
+    /// Atom 1.0
+    Atom,
+}
+
+/// Media attached to an entry, like a podcast episode
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Enclosure {
+    pub url: String,
+    pub mime_type: Option<String>,
+    /// Size in bytes
+    pub length: Option<u64>,
+}
+
+/// An entry of a feed. Dates are kept as written, RFC 822 for RSS and RFC 3339 for Atom
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FeedEntry {
+    pub id: Option<String>,
+    pub link: String,
+    pub title: String,
+    pub published: Option<String>,
+    pub author: Option<String>,
+    /// Summary as given by the feed, it may hold HTML
+    pub summary: Option<String>,
+    pub enclosure: Option<Enclosure>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Feed {
+    pub format: FeedFormat,
+    pub title: String,
+    /// Home page of the feed
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub entries: Vec<FeedEntry>,
+}
+
+/// Feeds advertised with `<link rel="alternate">` in a page
+pub fn discover_feeds(html: &str, base_url: &str) -> Vec<FeedLink> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel][type][href]").unwrap();
+    let mut feeds: Vec<FeedLink> = Vec::new();
+    for link in document.select(&selector) {
+        let alternate = link
+            .attr("rel")
+            .unwrap_or("")
+            .split_whitespace()
+            .any(|rel| rel.eq_ignore_ascii_case("alternate"));
+        let mime_type = link.attr("type").unwrap_or("").trim().to_lowercase();
+        if !alternate || !FEED_TYPES.contains(&mime_type.as_str()) {
+            continue;
+        }
+        let url = absolutify(base_url, link.attr("href").unwrap_or("").trim());
+        if url.is_empty() || feeds.iter().any(|f| f.url == url) {
+            continue;
+        }
+        feeds.push(FeedLink {
+            url,
+            title: link.attr("title").map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+            mime_type,
+        });
+    }
+    feeds
+}
+
+/// URLs worth trying for a feed: the advertised ones first, then common paths
+pub fn feed_candidates(html: &str, base_url: &str) -> Vec<String> {
+    let mut candidates: Vec<String> = discover_feeds(html, base_url)
+        .into_iter()
+        // JSON feeds are not parsed
+        .filter(|feed| feed.mime_type != "application/feed+json")
+        .map(|feed| feed.url)
+        .collect();
+    for path in COMMON_FEED_PATHS {
+        let url = absolutify(base_url, path);
+        if !url.is_empty() && !candidates.contains(&url) {
+            candidates.push(url);
+        }
+    }
+    candidates
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, names: &[&str]) -> Option<Node<'a, 'input>> {
+    names
+        .iter()
+        .find_map(|name| node.children().find(|c| c.is_element() && c.tag_name().name() == *name))
+}
+
+// Text of the first child named after one of `names`, in priority order
+fn child_text(node: Node, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        node.children()
+            .filter(|c| c.is_element() && c.tag_name().name() == *name)
+            .map(|c| {
+                c.descendants()
+                    .filter(|d| d.is_text())
+                    .filter_map(|d| d.text())
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .find(|text| !text.is_empty())
+    })
+}
+
+fn rss_entry(item: Node, feed_url: &str) -> FeedEntry {
+    let guid = child(item, &["guid"]);
+    let permalink = guid
+        .filter(|g| g.attribute("isPermaLink") != Some("false"))
+        .and_then(|g| g.text())
+        .map(str::trim);
+    let link = child_text(item, &["link"])
+        .or_else(|| item.attribute(("http://www.w3.org/1999/02/22-rdf-syntax-ns#", "about")).map(str::to_string))
+        .or_else(|| permalink.map(str::to_string))
+        .unwrap_or_default();
+    let enclosure = child(item, &["enclosure"]).and_then(|e| {
+        Some(Enclosure {
+            url: absolutify(feed_url, e.attribute("url")?.trim()),
+            mime_type: e.attribute("type").map(str::to_string),
+            length: e.attribute("length").and_then(|l| l.trim().parse().ok()),
+        })
+    });
+    FeedEntry {
+        id: child_text(item, &["guid"]),
+        link: absolutify(feed_url, &link),
+        title: child_text(item, &["title"]).unwrap_or_default(),
+        published: child_text(item, &["pubDate", "date", "published", "updated"]),
+        author: child_text(item, &["creator", "author"]),
+        summary: child_text(item, &["description", "encoded"]),
+        enclosure,
+    }
+}
+
+fn atom_link<'a, 'input>(entry: Node<'a, 'input>, rel: &str) -> Option<Node<'a, 'input>> {
+    entry.children().find(|c| {
+        c.is_element() && c.tag_name().name() == "link" && c.attribute("rel").unwrap_or("alternate") == rel
+    })
+}
+
+fn atom_entry(entry: Node, feed_url: &str) -> FeedEntry {
+    let link = atom_link(entry, "alternate")
+        .or_else(|| child(entry, &["link"]))
+        .and_then(|l| l.attribute("href"))
+        .unwrap_or("");
+    let enclosure = atom_link(entry, "enclosure").and_then(|e| {
+        Some(Enclosure {
+            url: absolutify(feed_url, e.attribute("href")?.trim()),
+            mime_type: e.attribute("type").map(str::to_string),
+            length: e.attribute("length").and_then(|l| l.trim().parse().ok()),
+        })
+    });
+    FeedEntry {
+        id: child_text(entry, &["id"]),
+        link: absolutify(feed_url, link.trim()),
+        title: child_text(entry, &["title"]).unwrap_or_default(),
+        published: child_text(entry, &["published", "updated", "issued", "modified"]),
+        author: child(entry, &["author"]).and_then(|a| child_text(a, &["name", "email"])),
+        summary: child_text(entry, &["summary", "content"]),
+        enclosure,
+    }
+}
+
+/// Parse an RSS 0.9x/2.0, RSS 1.0 (RDF) or Atom document.
+///
+/// Relative links are resolved against `feed_url`.
+pub fn parse_feed(xml: &str, feed_url: &str) -> Result<Feed> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(xml.trim_start_matches('\u{feff}').trim_start(), options)
+        .map_err(|e| Error::AppError(format!("Invalid feed {}: {}", feed_url, e)))?;
+    let root = document.root_element();
+
+    let feed = match root.tag_name().name() {
+        "rss" => {
+            let channel = child(root, &["channel"]).ok_or(Error::NullError("Channel".to_string()))?;
+            Feed {
+                format: FeedFormat::Rss,
+                title: child_text(channel, &["title"]).unwrap_or_default(),
+                link: child_text(channel, &["link"]).map(|l| absolutify(feed_url, &l)),
+                description: child_text(channel, &["description"]),
+                entries: channel
+                    .children()
+                    .filter(|c| c.is_element() && c.tag_name().name() == "item")
+                    .map(|item| rss_entry(item, feed_url))
+                    .collect(),
+            }
+        }
+        "RDF" => {
+            let channel = child(root, &["channel"]);
+            Feed {
+                format: FeedFormat::Rdf,
+                title: channel.and_then(|c| child_text(c, &["title"])).unwrap_or_default(),
+                link: channel
+                    .and_then(|c| child_text(c, &["link"]))
+                    .map(|l| absolutify(feed_url, &l)),
+                description: channel.and_then(|c| child_text(c, &["description"])),
+                // RSS 1.0 items are siblings of the channel
+                entries: root
+                    .children()
+                    .filter(|c| c.is_element() && c.tag_name().name() == "item")
+                    .map(|item| rss_entry(item, feed_url))
+                    .collect(),
+            }
+        }
+        "feed" => Feed {
+            format: FeedFormat::Atom,
+            title: child_text(root, &["title"]).unwrap_or_default(),
+            link: atom_link(root, "alternate")
+                .and_then(|l| l.attribute("href"))
+                .map(|l| absolutify(feed_url, l.trim())),
+            description: child_text(root, &["subtitle"]),
+            entries: root
+                .children()
+                .filter(|c| c.is_element() && c.tag_name().name() == "entry")
+                .map(|entry| atom_entry(entry, feed_url))
+                .collect(),
+        },
+        other => return Err(Error::AppError(format!("Not a feed {}: <{}>", feed_url, other))),
+    };
+    Ok(feed)
+}
+
+/// Decode the bytes of a feed.
+///
+/// The charset comes from a byte order mark, then the `Content-Type` header,
+/// then the `<?xml encoding>` declaration; UTF-8 otherwise.
+pub fn decode_feed(bytes: &[u8], content_type: Option<&str>) -> String {
+    let from_header = content_type
+        .and_then(|value| CONTENT_TYPE_CHARSET.captures(value))
+        .and_then(|caps| Encoding::for_label(caps[1].as_bytes()));
+    // The declaration is ASCII in every encoding a feed may use but UTF-16
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(256)]);
+    let from_declaration = XML_ENCODING
+        .captures(&head)
+        .and_then(|caps| Encoding::for_label(caps[1].as_bytes()));
+    let encoding = Encoding::for_bom(bytes)
+        .map(|(encoding, _)| encoding)
+        .or(from_header)
+        .or(from_declaration)
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Fetch and parse a feed
+pub async fn fetch_feed(url: &str, options: Option<RetrieveOptions>) -> Result<Feed> {
+    let (bytes, content_type) = retrieve_with_type(url, options).await?;
+    parse_feed(&decode_feed(&bytes, content_type.as_deref()), url)
+}
+
+/// Fetch the first feed that parses among the candidates of a page
+pub async fn find_feed(html: &str, base_url: &str, options: Option<RetrieveOptions>) -> Result<Feed> {
+    for url in feed_candidates(html, base_url) {
+        if let Ok(feed) = fetch_feed(&url, options.clone()).await {
+            return Ok(feed);
+        }
+    }
+    Err(Error::NullError("Feed".to_string()))
+}
+
+/// Run the full extraction over the link of each entry, in order
+pub async fn extract_entries(
+    feed: &Feed,
+    parser_opts: &ParseOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> Vec<(FeedEntry, Result<ParsedContent>)> {
+    let mut results = Vec::with_capacity(feed.entries.len());
+    for entry in &feed.entries {
+        let parsed = if entry.link.is_empty() {
+            Err(Error::NullError("Link".to_string()))
+        } else {
+            crate::extract_url(&entry.link, parser_opts, fetch_opts.clone()).await
+        };
+        results.push((entry.clone(), parsed));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_decode_feed() {
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n\
+            <rss version=\"2.0\"><channel><title>Caf\xe9 cr\xe8me</title></channel></rss>";
+        let xml = decode_feed(latin1, None);
+        assert!(xml.contains("Caf\u{e9} cr\u{e8}me"));
+        assert_eq!(parse_feed(&xml, "https://somewhere.com/rss").unwrap().title, "Caf\u{e9} cr\u{e8}me");
+
+        // The header wins over the declaration
+        let header = decode_feed(
+            b"<?xml version=\"1.0\" encoding=\"utf-8\"?><a>\xe9</a>",
+            Some("application/rss+xml; charset=windows-1252"),
+        );
+        assert!(header.contains("<a>\u{e9}</a>"));
+
+        let utf8 = decode_feed("<rss><channel><title>Caf\u{e9}</title></channel></rss>".as_bytes(), Some("text/xml"));
+        assert!(utf8.contains("Caf\u{e9}"));
+        assert!(decode_feed(b"\xef\xbb\xbf<rss/>", Some("text/xml; charset=iso-8859-1")).starts_with("<rss/>"));
+    }
+
+    #[test]
+    fn test_discover_feeds() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let feeds = discover_feeds(&html, "https://somewhere.com/path");
+        assert_eq!(
+            feeds,
+            vec![FeedLink {
+                url: "https://somewhere.com/atom.xml".to_string(),
+                title: Some("ArticleParser".to_string()),
+                mime_type: "application/atom+xml".to_string(),
+            }]
+        );
+
+        let candidates = feed_candidates(&html, "https://somewhere.com/path");
+        assert_eq!(candidates[0], "https://somewhere.com/atom.xml");
+        assert_eq!(candidates[1], "https://somewhere.com/feed");
+        assert_eq!(candidates.iter().filter(|c| c.ends_with("/atom.xml")).count(), 1);
+    }
+
+    #[test]
+    fn test_parse_rss() {
+        let xml = r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <channel>
+                <title>Somewhere News</title>
+                <link>https://somewhere.com/</link>
+                <description>All the news</description>
+                <item>
+                  <title>First story</title>
+                  <link>/news/first</link>
+                  <pubDate>Tue, 10 Jun 2025 04:00:00 GMT</pubDate>
+                  <dc:creator>Ada Lovelace</dc:creator>
+                  <description><![CDATA[<p>Short <b>summary</b></p>]]></description>
+                  <enclosure url="https://cdn.somewhere.com/ep1.mp3" type="audio/mpeg" length="12345"/>
+                </item>
+                <item>
+                  <title>Second story</title>
+                  <guid>https://somewhere.com/news/second</guid>
+                </item>
+              </channel>
+            </rss>"#;
+        let feed = parse_feed(xml, "https://somewhere.com/rss.xml").unwrap();
+        assert_eq!(feed.format, FeedFormat::Rss);
+        assert_eq!(feed.title, "Somewhere News");
+        assert_eq!(feed.entries.len(), 2);
+
+        let first = &feed.entries[0];
+        assert_eq!(first.link, "https://somewhere.com/news/first");
+        assert_eq!(first.published.as_deref(), Some("Tue, 10 Jun 2025 04:00:00 GMT"));
+        assert_eq!(first.author.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(first.summary.as_deref(), Some("<p>Short <b>summary</b></p>"));
+        assert_eq!(
+            first.enclosure,
+            Some(Enclosure {
+                url: "https://cdn.somewhere.com/ep1.mp3".to_string(),
+                mime_type: Some("audio/mpeg".to_string()),
+                length: Some(12345),
+            })
+        );
+        assert_eq!(feed.entries[1].link, "https://somewhere.com/news/second");
+    }
+
+    #[test]
+    fn test_parse_rdf() {
+        let xml = r#"<?xml version="1.0"?>
+            <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+                     xmlns="http://purl.org/rss/1.0/"
+                     xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <channel rdf:about="https://somewhere.com/">
+                <title>Somewhere</title>
+                <link>https://somewhere.com/</link>
+              </channel>
+              <item rdf:about="https://somewhere.com/rdf-story">
+                <title>RDF story</title>
+                <dc:date>2025-06-10T04:00:00Z</dc:date>
+              </item>
+            </rdf:RDF>"#;
+        let feed = parse_feed(xml, "https://somewhere.com/index.rdf").unwrap();
+        assert_eq!(feed.format, FeedFormat::Rdf);
+        assert_eq!(feed.entries[0].link, "https://somewhere.com/rdf-story");
+        assert_eq!(feed.entries[0].published.as_deref(), Some("2025-06-10T04:00:00Z"));
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+              <title>Somewhere</title>
+              <subtitle>Stories</subtitle>
+              <link rel="self" href="https://somewhere.com/atom.xml"/>
+              <link href="https://somewhere.com/"/>
+              <entry>
+                <id>tag:somewhere.com,2025:1</id>
+                <title type="html">Atom story</title>
+                <link rel="alternate" href="https://somewhere.com/atom-story"/>
+                <link rel="enclosure" href="/video.mp4" type="video/mp4"/>
+                <updated>2025-06-11T08:00:00Z</updated>
+                <published>2025-06-10T08:00:00Z</published>
+                <author><name>Grace Hopper</name></author>
+                <summary>Summary text</summary>
+              </entry>
+            </feed>"#;
+        let feed = parse_feed(xml, "https://somewhere.com/atom.xml").unwrap();
+        assert_eq!(feed.format, FeedFormat::Atom);
+        assert_eq!(feed.link.as_deref(), Some("https://somewhere.com/"));
+        let entry = &feed.entries[0];
+        assert_eq!(entry.id.as_deref(), Some("tag:somewhere.com,2025:1"));
+        assert_eq!(entry.link, "https://somewhere.com/atom-story");
+        assert_eq!(entry.published.as_deref(), Some("2025-06-10T08:00:00Z"));
+        assert_eq!(entry.author.as_deref(), Some("Grace Hopper"));
+        assert_eq!(entry.summary.as_deref(), Some("Summary text"));
+        assert_eq!(
+            entry.enclosure.as_ref().map(|e| e.url.as_str()),
+            Some("https://somewhere.com/video.mp4")
+        );
+
+        assert!(parse_feed("<html><body/></html>", "https://somewhere.com/").is_err());
+        assert!(parse_feed("not xml", "https://somewhere.com/").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::linker::absolutify;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Where a site icon was declared
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconKind {
+    /// `<link rel="icon">` or `rel="shortcut icon"`
+    Icon,
+    AppleTouchIcon,
+    /// Monochrome Safari pinned tab icon
+    MaskIcon,
+    /// `icons` of the web app manifest
+    Manifest,
+    /// `<meta name="msapplication-TileImage">`
+    TileImage,
+    /// `/favicon.ico` at the site root, which may not exist
+    Fallback,
+}
+
+/// A site icon candidate
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SiteIcon {
+    pub url: String,
+    pub kind: IconKind,
+    /// Declared `sizes` as width and height; empty when unknown
+    pub sizes: Vec<(u32, u32)>,
+    /// `sizes="any"` or an SVG, which scale to any size
+    pub scalable: bool,
+    pub mime_type: Option<String>,
+    /// Only drawable in a single color (mask icons, `purpose: monochrome`)
+    pub monochrome: bool,
+}
+
+/// Parse a `sizes` attribute such as `"16x16 32x32"` or `"any"`
+fn parse_sizes(sizes: &str) -> (Vec<(u32, u32)>, bool) {
+    let mut parsed = Vec::new();
+    let mut any = false;
+    for size in sizes.split_whitespace() {
+        if size.eq_ignore_ascii_case("any") {
+            any = true;
+            continue;
+        }
+        if let Some((w, h)) = size.to_lowercase().split_once('x')
+            && let (Ok(w), Ok(h)) = (w.parse(), h.parse())
+        {
+            parsed.push((w, h));
+        }
+    }
+    (parsed, any)
+}
+
+fn is_svg(url: &str, mime_type: Option<&str>) -> bool {
+    mime_type.is_some_and(|t| t.contains("svg"))
+        || url
+            .split(['?', '#'])
+            .next()
+            .is_some_and(|path| path.to_lowercase().ends_with(".svg"))
+}
+
+fn icon(url: String, kind: IconKind, sizes: Option<&str>, mime_type: Option<&str>) -> SiteIcon {
+    let (sizes, any) = parse_sizes(sizes.unwrap_or(""));
+    SiteIcon {
+        scalable: any || is_svg(&url, mime_type),
+        monochrome: kind == IconKind::MaskIcon,
+        url,
+        kind,
+        sizes,
+        mime_type: mime_type.map(str::to_string),
+    }
+}
+
+fn push_unique(icons: &mut Vec<SiteIcon>, candidate: SiteIcon) {
+    if candidate.url.is_empty() {
+        return;
+    }
+    match icons
+        .iter_mut()
+        .find(|i| i.url == candidate.url && i.kind == candidate.kind)
+    {
+        Some(existing) => {
+            for size in candidate.sizes {
+                if !existing.sizes.contains(&size) {
+                    existing.sizes.push(size);
+                }
+            }
+            existing.scalable |= candidate.scalable;
+        }
+        None => icons.push(candidate),
+    }
+}
+
+/// Collect the icons declared in the page, plus the `/favicon.ico` fallback
+pub fn extract_icons(html: &str, base_url: &str) -> Vec<SiteIcon> {
+    let document = Html::parse_document(html);
+    let mut icons = Vec::new();
+
+    let link_selector = Selector::parse("link[rel][href]").unwrap();
+    for link in document.select(&link_selector) {
+        let rel = link.attr("rel").unwrap_or("").to_lowercase();
+        let rels: Vec<&str> = rel.split_whitespace().collect();
+        let kind = if rels.contains(&"apple-touch-icon") || rels.contains(&"apple-touch-icon-precomposed") {
+            IconKind::AppleTouchIcon
+        } else if rels.contains(&"mask-icon") {
+            IconKind::MaskIcon
+        } else if rels.contains(&"icon") {
+            IconKind::Icon
+        } else {
+            continue;
+        };
+        let url = absolutify(base_url, link.attr("href").unwrap_or("").trim());
+        push_unique(&mut icons, icon(url, kind, link.attr("sizes"), link.attr("type")));
+    }
+
+    let meta_selector = Selector::parse("meta[name][content]").unwrap();
+    for meta in document.select(&meta_selector) {
+        if meta
+            .attr("name")
+            .is_some_and(|n| n.eq_ignore_ascii_case("msapplication-TileImage"))
+        {
+            let url = absolutify(base_url, meta.attr("content").unwrap_or("").trim());
+            // Windows tiles are 144px unless stated otherwise
+            push_unique(&mut icons, icon(url, IconKind::TileImage, Some("144x144"), None));
+        }
+    }
+
+    let fallback = absolutify(base_url, "/favicon.ico");
+    if !icons.iter().any(|i| i.url == fallback) {
+        push_unique(&mut icons, icon(fallback, IconKind::Fallback, None, None));
+    }
+    icons
+}
+
+/// Read the `icons` of a web app manifest, resolving them against `manifest_url`
+pub fn manifest_icons(manifest: &Value, manifest_url: &str) -> Vec<SiteIcon> {
+    let Some(Value::Array(entries)) = manifest.get("icons") else {
+        return Vec::new();
+    };
+    let mut icons = Vec::new();
+    for entry in entries {
+        let Some(src) = entry.get("src").and_then(Value::as_str) else {
+            continue;
+        };
+        let url = absolutify(manifest_url, src.trim());
+        let mut candidate = icon(
+            url,
+            IconKind::Manifest,
+            entry.get("sizes").and_then(Value::as_str),
+            entry.get("type").and_then(Value::as_str),
+        );
+        candidate.monochrome = entry
+            .get("purpose")
+            .and_then(Value::as_str)
+            .is_some_and(|p| p.split_whitespace().all(|p| p == "monochrome"));
+        push_unique(&mut icons, candidate);
+    }
+    icons
+}
+
+// Size assumed for icons that declare none
+fn assumed_size(icon: &SiteIcon) -> u32 {
+    match icon.kind {
+        IconKind::AppleTouchIcon => 180,
+        IconKind::TileImage => 144,
+        _ => 16,
+    }
+}
+
+// Lower is better: exact sizes first, then scalable icons, then the smallest
+// larger icon, then the largest smaller one. Monochrome and fallback icons come last.
+fn cost(icon: &SiteIcon, size: u32) -> f64 {
+    let size = size.max(1) as f64;
+    let fit = |side: u32| {
+        let side = side as f64;
+        if side == size {
+            0.0
+        } else if side > size {
+            2.0 + (side - size) / size
+        } else {
+            10.0 + (size - side) / size
+        }
+    };
+    let mut cost = if icon.scalable {
+        1.0
+    } else if icon.sizes.is_empty() {
+        fit(assumed_size(icon))
+    } else {
+        icon.sizes
+            .iter()
+            .map(|(w, h)| fit(*w.min(h)))
+            .fold(f64::MAX, f64::min)
+    };
+    if icon.monochrome {
+        cost += 20.0;
+    }
+    if icon.kind == IconKind::Fallback {
+        cost += 30.0;
+    }
+    cost
+}
+
+/// Pick the icon that best fits a square of `size` pixels
+pub fn choose_icon(icons: &[SiteIcon], size: u32) -> Option<&SiteIcon> {
+    icons
+        .iter()
+        .min_by(|a, b| cost(a, size).total_cmp(&cost(b, size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const PAGE: &str = r##"<html><head>
+        <link rel="shortcut icon" href="/favicon-16.png" sizes="16x16">
+        <link rel="icon" href="/favicon-32.png" sizes="32x32" type="image/png">
+        <link rel="icon" href="/favicon-96.png" sizes="96x96 128x128">
+        <link rel="apple-touch-icon" href="/apple-touch-icon.png">
+        <link rel="mask-icon" href="/safari.svg" color="#000">
+        <meta name="msapplication-TileImage" content="/tile.png">
+        </head><body></body></html>"##;
+
+    #[test]
+    fn test_extract_icons() {
+        let icons = extract_icons(PAGE, "https://some.where/story");
+        let kinds: Vec<IconKind> = icons.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                IconKind::Icon,
+                IconKind::Icon,
+                IconKind::Icon,
+                IconKind::AppleTouchIcon,
+                IconKind::MaskIcon,
+                IconKind::TileImage,
+                IconKind::Fallback,
+            ]
+        );
+        assert_eq!(icons[2].sizes, vec![(96, 96), (128, 128)]);
+        assert!(icons[4].scalable && icons[4].monochrome);
+        assert_eq!(icons[6].url, "https://some.where/favicon.ico");
+
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let icons = extract_icons(&html, "https://somewhere.com/path");
+        assert_eq!(icons.len(), 1);
+        assert_eq!(icons[0].kind, IconKind::Icon);
+    }
+
+    #[test]
+    fn test_choose_icon() {
+        let icons = extract_icons(PAGE, "https://some.where/story");
+        let url = |size| choose_icon(&icons, size).map(|i| i.url.as_str());
+        assert_eq!(url(16), Some("https://some.where/favicon-16.png"));
+        assert_eq!(url(64), Some("https://some.where/favicon-96.png"));
+        assert_eq!(url(150), Some("https://some.where/apple-touch-icon.png"));
+        assert_eq!(url(512), Some("https://some.where/apple-touch-icon.png"));
+
+        let only_fallback = extract_icons("<html></html>", "https://some.where/");
+        assert_eq!(
+            choose_icon(&only_fallback, 64).map(|i| i.url.as_str()),
+            Some("https://some.where/favicon.ico")
+        );
+    }
+
+    #[test]
+    fn test_manifest_icons() {
+        let manifest: Value = serde_json::from_str(
+            r#"{"icons": [
+                {"src": "icons/192.png", "sizes": "192x192", "type": "image/png"},
+                {"src": "icons/logo.svg", "sizes": "any", "purpose": "monochrome"}
+            ]}"#,
+        )
+        .unwrap();
+        let icons = manifest_icons(&manifest, "https://some.where/app/manifest.json");
+        assert_eq!(icons.len(), 2);
+        assert_eq!(icons[0].url, "https://some.where/app/icons/192.png");
+        assert_eq!(icons[0].sizes, vec![(192, 192)]);
+        assert!(icons[1].scalable && icons[1].monochrome);
+        assert_eq!(choose_icon(&icons, 64).map(|i| i.url.as_str()), Some(icons[0].url.as_str()));
+    }
+}
@@ -0,0 +1,575 @@
+use crate::extract::ld_json_objects;
+use crate::linker::absolutify;
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// An image of the article
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ArticleImage {
+    /// Absolute URL
+    pub url: String,
+    pub alt: String,
+    pub caption: Option<String>,
+    /// Photographer or agency line, split off the caption
+    pub credit: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Whether this is the lead image, `ParsedContent::image`
+    pub is_lead: bool,
+}
+
+lazy_static! {
+    static ref CAPTION_CLASS: Regex = Regex::new(r"(?i)(caption|cutline|wp-caption-text|legend)").unwrap();
+    static ref CREDIT_CLASS: Regex = Regex::new(r"(?i)(credit|copyright|photographer|attribution|byline|source)").unwrap();
+    static ref NEGATIVE_IMAGE: Regex = Regex::new(
+        r"(?i)(logo|avatar|gravatar|sprite|icon|favicon|badge|emoji|button|spacer|blank\.|pixel|tracking|beacon|1x1|/ads?/|[-_.]ads?[-_.]|advert|banner-ad|doubleclick|profile-pic|author-photo)"
+    )
+    .unwrap();
+    static ref POSITIVE_IMAGE: Regex =
+        Regex::new(r"(?i)(hero|lead|featured|cover|main-image|wp-post-image|article-image|story-image|og-image)").unwrap();
+    static ref CREDIT_TEXT: Regex = Regex::new(
+        r"(?i)\s*[(\[]?\s*(?:(?:photo(?:graph)?|image|picture|illustration)s?\s*(?:by|credit)?\s*:|credit\s*:|©)\s*([^)\]]+?)\s*[)\]]?\s*$"
+    )
+    .unwrap();
+    static ref FIGCAPTION: Selector = Selector::parse("figcaption").unwrap();
+}
+
+const CHROME_TAGS: &[&str] = &["nav", "footer", "aside", "header"];
+
+/// Candidates scoring below this are never picked as the lead image
+const MIN_LEAD_SCORE: f64 = 10.0;
+/// How many images above the content are still considered
+const MAX_IMAGES_ABOVE: usize = 3;
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn element_text(element: &ElementRef) -> String {
+    normalize_text(&element.text().collect::<Vec<_>>().join(" "))
+}
+
+fn class_matches(element: &ElementRef, re: &Regex) -> bool {
+    let value = format!(
+        "{} {}",
+        element.attr("class").unwrap_or(""),
+        element.attr("id").unwrap_or("")
+    );
+    re.is_match(&value)
+}
+
+fn parse_dimension(value: Option<&str>) -> Option<u32> {
+    value?
+        .trim()
+        .trim_end_matches("px")
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+}
+
+/// Source of an `img`: `src`, lazy-loading attributes, then the first `srcset` entry
+pub fn image_source(img: &ElementRef) -> Option<String> {
+    ["src", "data-src", "data-lazy-src", "data-original"]
+        .iter()
+        .filter_map(|attr| img.attr(attr))
+        .map(str::trim)
+        .find(|src| !src.is_empty() && !src.starts_with("data:"))
+        .or_else(|| {
+            img.attr("srcset")?
+                .split(',')
+                .next()?
+                .split_whitespace()
+                .next()
+        })
+        .map(str::to_string)
+}
+
+/// Split a trailing credit line, such as "(Photo: AP)", off a caption
+fn split_credit(caption: &str) -> (String, Option<String>) {
+    match CREDIT_TEXT.captures(caption) {
+        Some(caps) => {
+            let credit = caps[1].trim().to_string();
+            let rest = caption[..caps.get(0).unwrap().start()].trim().to_string();
+            (rest, (!credit.is_empty()).then_some(credit))
+        }
+        None => (caption.to_string(), None),
+    }
+}
+
+// Caption and credit of a figure, with credit elements taken out of the caption text
+fn figure_caption(figcaption: &ElementRef) -> (Option<String>, Option<String>) {
+    let credit_elements: Vec<ElementRef> = figcaption
+        .descendants()
+        .skip(1)
+        .filter_map(ElementRef::wrap)
+        .filter(|e| class_matches(e, &CREDIT_CLASS))
+        .collect();
+    let mut credit = credit_elements
+        .first()
+        .map(element_text)
+        .filter(|c| !c.is_empty());
+
+    let caption: String = figcaption
+        .descendants()
+        .filter_map(|node| {
+            let text = node.value().as_text()?;
+            let in_credit = node
+                .ancestors()
+                .any(|a| credit_elements.iter().any(|c| c.id() == a.id()));
+            (!in_credit).then(|| text.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (caption, text_credit) = split_credit(&normalize_text(&caption));
+    credit = credit.or(text_credit);
+    ((!caption.is_empty()).then_some(caption), credit)
+}
+
+// The caption element right after the image or its wrapper, for markup without <figure>
+fn adjacent_caption<'a>(img: &ElementRef<'a>) -> Option<ElementRef<'a>> {
+    let mut node = **img;
+    for _ in 0..3 {
+        let sibling = node
+            .next_siblings()
+            .filter_map(ElementRef::wrap)
+            .next();
+        if let Some(sibling) = sibling
+            && class_matches(&sibling, &CAPTION_CLASS)
+        {
+            return Some(sibling);
+        }
+        node = node.parent()?;
+    }
+    None
+}
+
+fn describe(img: &ElementRef, base_url: &str) -> Option<ArticleImage> {
+    let url = absolutify(base_url, &image_source(img)?);
+    if url.is_empty() {
+        return None;
+    }
+    let figcaption = img
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|a| a.value().name() == "figure")
+        .and_then(|figure| figure.select(&FIGCAPTION).next());
+    let (caption, credit) = match figcaption {
+        Some(figcaption) => figure_caption(&figcaption),
+        None => match adjacent_caption(img) {
+            Some(element) => figure_caption(&element),
+            None => (None, None),
+        },
+    };
+    let caption = caption.or_else(|| {
+        img.attr("title")
+            .map(normalize_text)
+            .filter(|t| !t.is_empty())
+    });
+
+    Some(ArticleImage {
+        url,
+        alt: normalize_text(img.attr("alt").unwrap_or("")),
+        caption,
+        credit,
+        width: parse_dimension(img.attr("width")),
+        height: parse_dimension(img.attr("height")),
+        is_lead: false,
+    })
+}
+
+fn meta_content(document: &Html, names: &[&str]) -> Option<String> {
+    let selector = Selector::parse("meta[content]").unwrap();
+    document
+        .select(&selector)
+        .find(|meta| {
+            meta.attr("property")
+                .or_else(|| meta.attr("name"))
+                .is_some_and(|p| names.contains(&p.to_lowercase().as_str()))
+        })
+        .and_then(|meta| meta.attr("content"))
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+/// Collect the images of the extracted content together with the lead image.
+///
+/// Captions, credits and dimensions are looked up in `page_html`, where the
+/// class names the sanitized `content` no longer has are still available. The
+/// lead image comes first and takes its dimensions from `og:image:width/height`
+/// when its markup declares none.
+pub fn extract_images(content: &str, page_html: &str, base_url: &str, lead: &str) -> Vec<ArticleImage> {
+    let selector = Selector::parse("img").unwrap();
+    let page = Html::parse_document(page_html);
+    let from_page: HashMap<String, ArticleImage> = page
+        .select(&selector)
+        .filter_map(|img| describe(&img, base_url))
+        .fold(HashMap::new(), |mut images, image| {
+            images.entry(image.url.clone()).or_insert(image);
+            images
+        });
+
+    let fragment = Html::parse_fragment(content);
+    let mut images: Vec<ArticleImage> = Vec::new();
+    for img in fragment.select(&selector) {
+        let Some(mut image) = describe(&img, base_url) else {
+            continue;
+        };
+        if images.iter().any(|i| i.url == image.url) {
+            continue;
+        }
+        if let Some(found) = from_page.get(&image.url) {
+            image.caption = found.caption.clone().or(image.caption);
+            image.credit = found.credit.clone().or(image.credit);
+            image.width = image.width.or(found.width);
+            image.height = image.height.or(found.height);
+            if image.alt.is_empty() {
+                image.alt = found.alt.clone();
+            }
+        }
+        images.push(image);
+    }
+
+    let lead = absolutify(base_url, lead);
+    if lead.is_empty() {
+        return images;
+    }
+    let mut lead_image = match images.iter().position(|i| i.url == lead) {
+        Some(index) => images.remove(index),
+        None => from_page.get(&lead).cloned().unwrap_or(ArticleImage {
+            url: lead,
+            alt: meta_content(&page, &["og:image:alt", "twitter:image:alt"]).unwrap_or_default(),
+            ..Default::default()
+        }),
+    };
+    lead_image.is_lead = true;
+    lead_image.width = lead_image
+        .width
+        .or_else(|| parse_dimension(meta_content(&page, &["og:image:width"]).as_deref()));
+    lead_image.height = lead_image
+        .height
+        .or_else(|| parse_dimension(meta_content(&page, &["og:image:height"]).as_deref()));
+    images.insert(0, lead_image);
+    images
+}
+
+/// Where a lead image candidate was found
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LeadSource {
+    JsonLd,
+    ImageSrc,
+    /// The n-th image of the content
+    Content(usize),
+    /// The n-th image above the content, counting upwards
+    Above(usize),
+}
+
+struct LeadCandidate {
+    url: String,
+    source: LeadSource,
+    width: Option<u32>,
+    height: Option<u32>,
+    hints: String,
+}
+
+fn json_image_urls(value: &Value, urls: &mut Vec<(String, Option<u32>, Option<u32>)>) {
+    match value {
+        Value::String(url) => urls.push((url.clone(), None, None)),
+        Value::Array(items) => items.iter().for_each(|item| json_image_urls(item, urls)),
+        Value::Object(map) => {
+            let dimension = |key: &str| match map.get(key) {
+                Some(Value::Number(n)) => n.as_u64().map(|n| n as u32),
+                Some(Value::String(s)) => parse_dimension(Some(s)),
+                _ => None,
+            };
+            if let Some(Value::String(url)) = map.get("url").or(map.get("contentUrl")) {
+                urls.push((url.clone(), dimension("width"), dimension("height")));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn score_candidate(candidate: &LeadCandidate) -> f64 {
+    let mut score = match candidate.source {
+        LeadSource::JsonLd => 40.0,
+        LeadSource::ImageSrc => 35.0,
+        // Earlier images of the content are likelier to illustrate the whole article
+        LeadSource::Content(n) => 30.0 - (n as f64 * 5.0).min(20.0),
+        LeadSource::Above(n) => 25.0 - n as f64 * 5.0,
+    };
+    if NEGATIVE_IMAGE.is_match(&candidate.hints) {
+        score -= 50.0;
+    }
+    if POSITIVE_IMAGE.is_match(&candidate.hints) {
+        score += 15.0;
+    }
+    let lower = candidate.url.to_lowercase();
+    let path = lower.split(['?', '#']).next().unwrap_or("");
+    if path.ends_with(".svg") || path.ends_with(".ico") {
+        score -= 30.0;
+    } else if path.ends_with(".gif") {
+        score -= 10.0;
+    }
+
+    if let (Some(width), Some(height)) = (candidate.width, candidate.height) {
+        let (w, h) = (width as f64, height as f64);
+        if width <= 2 || height <= 2 {
+            // Tracking pixels
+            score -= 100.0;
+        } else if width < 100 || height < 100 {
+            score -= 40.0;
+        } else if w * h >= 300.0 * 200.0 {
+            score += 20.0 + ((w * h) / 100_000.0).min(10.0);
+        }
+        let ratio = w / h;
+        if !(0.33..=3.0).contains(&ratio) {
+            // Banners and skyscrapers
+            score -= 25.0;
+        } else if (1.2..=2.0).contains(&ratio) {
+            score += 10.0;
+        }
+    } else if let Some(side) = candidate.width.or(candidate.height) {
+        if side < 100 {
+            score -= 40.0;
+        } else if side >= 300 {
+            score += 10.0;
+        }
+    }
+    score
+}
+
+fn in_chrome(element: &ElementRef) -> bool {
+    element.ancestors().filter_map(ElementRef::wrap).any(|a| {
+        CHROME_TAGS.contains(&a.value().name())
+            // A header inside the article holds its hero image, not site chrome
+            && !(a.value().name() == "header"
+                && a.ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .any(|b| matches!(b.value().name(), "article" | "main")))
+    })
+}
+
+fn element_hints(img: &ElementRef, url: &str) -> String {
+    let mut hints = vec![url.to_string()];
+    hints.extend(
+        ["class", "id", "alt"]
+            .iter()
+            .filter_map(|attr| img.attr(attr))
+            .map(str::to_string),
+    );
+    // Wrapper classes such as "site-logo" or "author-avatar"
+    hints.extend(
+        img.ancestors()
+            .filter_map(ElementRef::wrap)
+            .take(2)
+            .filter_map(|a| a.attr("class"))
+            .map(str::to_string),
+    );
+    hints.join(" ")
+}
+
+/// Pick a lead image for pages whose metadata has none.
+///
+/// Candidates come from JSON-LD `image` properties, `<link rel="image_src">`, the
+/// images of the extracted `content` and the few images right above it in
+/// `page_html`. They are scored on where they were found, declared dimensions,
+/// aspect ratio and hints in file and class names; nothing is downloaded.
+pub fn pick_lead_image(page_html: &str, content: &str, base_url: &str) -> Option<String> {
+    let document = Html::parse_document(page_html);
+    let img_selector = Selector::parse("img").unwrap();
+    let mut candidates = Vec::new();
+
+    for object in ld_json_objects(&document) {
+        let Some(image) = object.get("image").or(object.get("thumbnailUrl")) else {
+            continue;
+        };
+        let mut urls = Vec::new();
+        json_image_urls(image, &mut urls);
+        for (url, width, height) in urls {
+            candidates.push(LeadCandidate {
+                hints: url.clone(),
+                url,
+                source: LeadSource::JsonLd,
+                width,
+                height,
+            });
+        }
+    }
+
+    let link_selector = Selector::parse(r#"link[rel="image_src"][href]"#).unwrap();
+    for link in document.select(&link_selector) {
+        let url = link.attr("href").unwrap_or("").to_string();
+        candidates.push(LeadCandidate {
+            hints: url.clone(),
+            url,
+            source: LeadSource::ImageSrc,
+            width: None,
+            height: None,
+        });
+    }
+
+    // Content images are described from the page so class names still count
+    let fragment = Html::parse_fragment(content);
+    let content_urls: Vec<String> = fragment
+        .select(&img_selector)
+        .filter_map(|img| image_source(&img))
+        .map(|src| absolutify(base_url, &src))
+        .collect();
+    let page_images: Vec<(String, ElementRef)> = document
+        .select(&img_selector)
+        .filter_map(|img| Some((absolutify(base_url, &image_source(&img)?), img)))
+        .collect();
+    let first_in_content = page_images
+        .iter()
+        .position(|(url, _)| content_urls.contains(url));
+
+    for (n, url) in content_urls.iter().enumerate() {
+        let img = page_images.iter().find(|(u, _)| u == url).map(|(_, img)| img);
+        candidates.push(LeadCandidate {
+            url: url.clone(),
+            source: LeadSource::Content(n),
+            width: img.and_then(|img| parse_dimension(img.attr("width"))),
+            height: img.and_then(|img| parse_dimension(img.attr("height"))),
+            hints: img.map_or(url.clone(), |img| element_hints(img, url)),
+        });
+    }
+    if let Some(first) = first_in_content {
+        let above = page_images[..first]
+            .iter()
+            .rev()
+            .filter(|(_, img)| !in_chrome(img))
+            .take(MAX_IMAGES_ABOVE);
+        for (n, (url, img)) in above.enumerate() {
+            candidates.push(LeadCandidate {
+                url: url.clone(),
+                source: LeadSource::Above(n),
+                width: parse_dimension(img.attr("width")),
+                height: parse_dimension(img.attr("height")),
+                hints: element_hints(img, url),
+            });
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter(|c| !c.url.trim().is_empty() && !c.url.starts_with("data:"))
+        .map(|c| (score_candidate(&c), c.url))
+        .filter(|(score, _)| *score >= MIN_LEAD_SCORE)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, url)| absolutify(base_url, &url))
+        .filter(|url| !url.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head>
+        <meta property="og:image" content="https://some.where/hero.jpg">
+        <meta property="og:image:width" content="1200">
+        <meta property="og:image:height" content="630">
+        <meta property="og:image:alt" content="Crowd at the harbour">
+        </head><body><article>
+            <figure>
+                <img src="/chart.png" alt="Chart" width="640" height="480">
+                <figcaption>Sales by year <span class="credit">Jane Doe / Agency</span></figcaption>
+            </figure>
+            <p>Text</p>
+            <div class="wp-caption"><img src="/boat.jpg" alt=""></div>
+            <p class="wp-caption-text">A boat at dawn. (Photo: John Roe)</p>
+            <img data-src="/lazy.jpg" title="Lazy loaded">
+        </article></body></html>"#;
+
+    #[test]
+    fn test_split_credit() {
+        assert_eq!(
+            split_credit("A boat. (Photo: John Roe)"),
+            ("A boat.".to_string(), Some("John Roe".to_string()))
+        );
+        assert_eq!(
+            split_credit("Skyline © Reuters"),
+            ("Skyline".to_string(), Some("Reuters".to_string()))
+        );
+        assert_eq!(split_credit("No credit"), ("No credit".to_string(), None));
+    }
+
+    #[test]
+    fn test_extract_images() {
+        // Sanitized content as produced by `cleanify`: no classes left
+        let content = r#"<figure><img src="https://some.where/chart.png" alt="Chart"><figcaption>Sales by year <span>Jane Doe / Agency</span></figcaption></figure><p>Text</p><div><img src="https://some.where/boat.jpg" alt=""></div><p>A boat at dawn. (Photo: John Roe)</p><img src="https://some.where/lazy.jpg">"#;
+        let images = extract_images(content, PAGE, "https://some.where/story", "https://some.where/hero.jpg");
+
+        assert_eq!(images.len(), 4);
+        assert_eq!(
+            images[0],
+            ArticleImage {
+                url: "https://some.where/hero.jpg".to_string(),
+                alt: "Crowd at the harbour".to_string(),
+                width: Some(1200),
+                height: Some(630),
+                is_lead: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            images[1],
+            ArticleImage {
+                url: "https://some.where/chart.png".to_string(),
+                alt: "Chart".to_string(),
+                caption: Some("Sales by year".to_string()),
+                credit: Some("Jane Doe / Agency".to_string()),
+                width: Some(640),
+                height: Some(480),
+                is_lead: false,
+            }
+        );
+        assert_eq!(images[2].caption.as_deref(), Some("A boat at dawn."));
+        assert_eq!(images[2].credit.as_deref(), Some("John Roe"));
+        assert_eq!(images[3].caption.as_deref(), Some("Lazy loaded"));
+    }
+
+    #[test]
+    fn test_lead_image_in_content() {
+        let content = r#"<img src="https://some.where/chart.png">"#;
+        let images = extract_images(content, PAGE, "https://some.where/story", "/chart.png");
+        assert_eq!(images.len(), 1);
+        assert!(images[0].is_lead);
+        assert_eq!(images[0].width, Some(640));
+    }
+
+    #[test]
+    fn test_pick_lead_image_from_content() {
+        let page = r#"<html><body>
+            <header><img src="/logo.png" class="site-logo" width="200" height="60"></header>
+            <article>
+                <img src="/hero.jpg" width="1200" height="675">
+                <p>Intro text of the story.</p>
+                <img src="/avatar-jane.jpg" width="48" height="48">
+                <img src="/track.gif" width="1" height="1">
+            </article></body></html>"#;
+        let content = r#"<p>Intro text of the story.</p><img src="https://some.where/avatar-jane.jpg"><img src="https://some.where/track.gif">"#;
+        assert_eq!(
+            pick_lead_image(page, content, "https://some.where/story").as_deref(),
+            Some("https://some.where/hero.jpg")
+        );
+    }
+
+    #[test]
+    fn test_pick_lead_image_from_json_ld() {
+        let page = r#"<html><head><script type="application/ld+json">
+            {"@type": "NewsArticle", "image": {"@type": "ImageObject", "url": "/lead.jpg", "width": 1600, "height": 900}}
+            </script><link rel="image_src" href="/icon-sprite.png"></head><body><p>Text</p></body></html>"#;
+        assert_eq!(
+            pick_lead_image(page, "<p>Text</p>", "https://some.where/story").as_deref(),
+            Some("https://some.where/lead.jpg")
+        );
+        assert_eq!(
+            pick_lead_image("<html><body><p>Text</p></body></html>", "<p>Text</p>", "https://some.where/"),
+            None
+        );
+    }
+}
@@ -5,49 +5,85 @@ mod parse_from_html;
 mod retrieve;
 mod normalizer;
 
+pub mod amp;
+pub mod blocks;
+pub mod canonical;
+pub mod classify;
 pub mod similarity;
+pub mod sitemap;
+pub mod comments;
+pub mod crawler;
+pub mod feed;
+pub mod strategy;
+pub mod tables;
+pub mod icons;
+pub mod images;
+pub mod manifest;
+pub mod outbound;
+pub mod outline;
+pub mod pagination;
+pub mod paywall;
+pub mod probe;
+pub mod psl;
+pub mod profile;
+pub mod quality;
+pub mod readability;
+pub mod tracking;
 pub mod transformation;
 mod utils;
 mod error;
 pub mod config;
-use html::get_charset;
 use linker::is_valid_url;
-use encoding_rs::Encoding;
 pub use parse_from_html::{parse_from_html, ParseOptions, ParsedContent};
 pub use retrieve::{retrieve, RetrieveOptions};
-use error::{Error, AppResult};
-
-fn buffer_to_string(buffer: &Vec<u8>) -> String {
-    let text = String::from_utf8_lossy(&buffer);
-    text.trim().to_string()
-}
+use error::AppResult;
 
 pub async fn extract_from_url(
     input_url: &str,
     parser_opts: ParseOptions,
     fetch_opts: Option<RetrieveOptions>,
+) -> AppResult<ParsedContent> {
+    extract_url(input_url, &parser_opts, fetch_opts).await
+}
+
+// Shared by `extract_from_url` and the modules extracting many URLs with the same options
+pub(crate) async fn extract_url(
+    input_url: &str,
+    parser_opts: &ParseOptions,
+    fetch_opts: Option<RetrieveOptions>,
 ) -> AppResult<ParsedContent> {
     if !is_valid_url(input_url) {
         let parsed = 
-            parse_from_html("", input_url, &parser_opts).await;
+            parse_from_html("", input_url, parser_opts).await;
         return parsed;
     }
-    let buffer: Vec<u8> = retrieve::retrieve(input_url, fetch_opts).await?;
-    let text = buffer_to_string(&buffer);
-    if text.is_empty() {
-        return Err(Error::NullError(input_url.to_string()));
-    }
-    let charset = get_charset(&text);
-    let (html, _, _) = if let Some(encoding) = Encoding::for_label(charset.as_bytes()) {
-        encoding.decode(&buffer)
+    let pages_opts = fetch_opts.clone();
+    let html = retrieve::retrieve_html(input_url, fetch_opts).await?;
+
+    let parsed = parse_from_html(&html, input_url, parser_opts).await;
+    // Pagination is followed on the page the content was read from
+    let amp_page = if parser_opts.amp_fallback {
+        amp::amp_fallback(&parsed, &html, input_url, parser_opts, pages_opts.clone()).await
     } else {
-        return Err(Error::UnsupportedEncoding(charset));
+        None
     };
-    
-    let parsed = 
-        parse_from_html(&html, input_url, &parser_opts).await;
-    
-    parsed
+    let (mut parsed, page_html, page_url) = match amp_page {
+        Some(amp_page) => (amp_page.parsed, amp_page.html, amp_page.url),
+        None => (parsed?, html.clone(), input_url.to_string()),
+    };
+    if parser_opts.follow_pagination {
+        parsed = pagination::follow_pages(parsed, &page_html, &page_url, parser_opts, pages_opts.clone())
+            .await?;
+    }
+    if parser_opts.fetch_manifest
+        && let Ok(site_manifest) = manifest::fetch_manifest(&html, input_url, pages_opts.clone()).await
+    {
+        manifest::apply_manifest(&mut parsed, site_manifest, parser_opts.icon_size);
+    }
+    if let Some(probe_opts) = &parser_opts.probe_images {
+        probe::fill_dimensions(&mut parsed.images, probe_opts, pages_opts).await;
+    }
+    Ok(parsed)
 }
 
 pub async fn extract_from_html(
@@ -2,6 +2,8 @@
 use std::borrow::BorrowMut;
 
 use crate::similarity::find_best_match;
+use crate::psl;
+use crate::tracking::strip_tracking;
 use scraper::{Html, Selector};
 
 /// Check if a URL is valid (http/https only)
@@ -20,96 +22,9 @@ pub fn choose_best_url(candidates: Vec<String>, title: &str) -> Option<String> {
     }
 }
 
-// Purify a URL by removing tracking parameters and fragment
+// Purify a URL by removing tracking parameters and fragment, see `tracking::TrackingRules`
 pub fn purify(url: &str) -> Option<String> {
-    if let Ok(mut parsed_url) = url::Url::parse(url) {
-        let blacklist_keys: std::collections::HashSet<&str> = [
-            "CNDID",
-            "__twitter_impression",
-            "_hsenc",
-            "_openstat",
-            "action_object_map",
-            "action_ref_map",
-            "action_type_map",
-            "amp",
-            "fb_action_ids",
-            "fb_action_types",
-            "fb_ref",
-            "fb_source",
-            "fbclid",
-            "ga_campaign",
-            "ga_content",
-            "ga_medium",
-            "ga_place",
-            "ga_source",
-            "ga_term",
-            "gs_l",
-            "hmb_campaign",
-            "hmb_medium",
-            "hmb_source",
-            "mbid",
-            "mc_cid",
-            "mc_eid",
-            "mkt_tok",
-            "referrer",
-            "spJobID",
-            "spMailingID",
-            "spReportId",
-            "spUserID",
-            "utm_brand",
-            "utm_campaign",
-            "utm_cid",
-            "utm_content",
-            "utm_int",
-            "utm_mailing",
-            "utm_medium",
-            "utm_name",
-            "utm_place",
-            "utm_pubreferrer",
-            "utm_reader",
-            "utm_social",
-            "utm_source",
-            "utm_swu",
-            "utm_term",
-            "utm_userid",
-            "utm_viz_id",
-            "wt_mc_o",
-            "yclid",
-            "WT.mc_id",
-            "WT.mc_ev",
-            "WT.srch",
-            "pk_source",
-            "pk_medium",
-            "pk_campaign",
-        ]
-        .iter()
-        .copied()
-        .collect();
-
-        // Remove fragment
-        parsed_url.set_fragment(None);
-
-        // Collect query pairs that are not blacklisted
-        let kept: Vec<(String, String)> = parsed_url
-            .query_pairs()
-            .filter(|(k, _)| !blacklist_keys.contains(k.as_ref()))
-            .map(|(k, v)| (k.into_owned(), v.into_owned()))
-            .collect();
-
-        // Rebuild query string
-        if kept.is_empty() {
-            parsed_url.set_query(None);
-        } else {
-            let mut pairs = parsed_url.query_pairs_mut();
-            pairs.clear();
-            for (k, v) in kept {
-                pairs.append_pair(&k, &v);
-            }
-        }
-
-        return Some(parsed_url.to_string());
-    }
-    None
+    strip_tracking(url)
 }
 
 // Convert a relative URL to an absolute URL
@@ -130,6 +45,16 @@ pub fn get_domain(url: &str) -> Option<String> {
         .map(|u| u.host_str().unwrap_or("").to_string())
 }
 
+// Get the registrable domain (`example.co.uk` for `news.example.co.uk`) from a URL,
+// the host itself for IP addresses and hosts that are a public suffix
+pub fn registrable_domain(url: &str) -> Option<String> {
+    let host = get_domain(url)?.to_lowercase();
+    if host.is_empty() {
+        return None;
+    }
+    Some(psl::registrable_domain(&host).unwrap_or(host))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,4 +177,19 @@ mod tests {
         let result = choose_best_url(urls.clone(), title);
         assert_eq!(result.unwrap(), urls[3]);
     }
+
+    #[test]
+    fn test_registrable_domain() {
+        let entries = vec![
+            ("https://www.somewhere.com/story", Some("somewhere.com")),
+            ("https://news.bbc.co.uk/1/hi", Some("bbc.co.uk")),
+            ("https://someone.github.io/blog", Some("someone.github.io")),
+            ("https://localhost:8080/", Some("localhost")),
+            ("http://127.0.0.1/page", Some("127.0.0.1")),
+            ("not a url", None),
+        ];
+        for (url, expected) in entries {
+            assert_eq!(registrable_domain(url).as_deref(), expected, "URL: {}", url);
+        }
+    }
 }
@@ -0,0 +1,180 @@
+use crate::error::{AppResult as Result, Error};
+use crate::icons::{choose_icon, manifest_icons, SiteIcon};
+use crate::linker::absolutify;
+use crate::parse_from_html::ParsedContent;
+use crate::retrieve::{retrieve, RetrieveOptions};
+use scraper::{Html, Selector};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Site identity read from a web app manifest
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct WebManifest {
+    /// Absolute URL the manifest was read from
+    pub url: String,
+    pub name: Option<String>,
+    pub short_name: Option<String>,
+    pub theme_color: Option<String>,
+    pub background_color: Option<String>,
+    /// Absolute `start_url`
+    pub start_url: Option<String>,
+    pub icons: Vec<SiteIcon>,
+}
+
+impl WebManifest {
+    /// Name to show for the site, `name` first
+    pub fn display_name(&self) -> Option<&str> {
+        self.name.as_deref().or(self.short_name.as_deref())
+    }
+}
+
+/// Absolute URL of the `<link rel="manifest">` of a page
+pub fn manifest_url(html: &str, base_url: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel][href]").unwrap();
+    document
+        .select(&selector)
+        .find(|link| {
+            link.attr("rel")
+                .unwrap_or("")
+                .split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("manifest"))
+        })
+        .map(|link| absolutify(base_url, link.attr("href").unwrap_or("").trim()))
+        .filter(|url| !url.is_empty())
+}
+
+fn text(manifest: &Value, key: &str) -> Option<String> {
+    manifest
+        .get(key)
+        .and_then(Value::as_str)
+        .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse a manifest, resolving its URLs against `manifest_url`
+pub fn parse_manifest(json: &str, manifest_url: &str) -> Result<WebManifest> {
+    let manifest: Value = serde_json::from_str(json.trim_start_matches('\u{feff}'))
+        .map_err(|e| Error::AppError(format!("Invalid manifest {}: {}", manifest_url, e)))?;
+    if !manifest.is_object() {
+        return Err(Error::AppError(format!("Invalid manifest {}", manifest_url)));
+    }
+    Ok(WebManifest {
+        url: manifest_url.to_string(),
+        name: text(&manifest, "name"),
+        short_name: text(&manifest, "short_name"),
+        theme_color: text(&manifest, "theme_color"),
+        background_color: text(&manifest, "background_color"),
+        start_url: text(&manifest, "start_url")
+            .map(|start| absolutify(manifest_url, &start))
+            .filter(|start| !start.is_empty()),
+        icons: manifest_icons(&manifest, manifest_url),
+    })
+}
+
+/// Fetch and parse the manifest linked from `html`
+pub async fn fetch_manifest(
+    html: &str,
+    base_url: &str,
+    options: Option<RetrieveOptions>,
+) -> Result<WebManifest> {
+    let url = manifest_url(html, base_url).ok_or(Error::NullError("Manifest".to_string()))?;
+    let bytes = retrieve(&url, options).await?;
+    parse_manifest(&String::from_utf8_lossy(&bytes), &url)
+}
+
+/// Merge a manifest into parsed content.
+///
+/// The manifest name replaces a `source` derived from the domain, its
+/// icons join the candidates and `favicon` is picked again for `icon_size`.
+pub fn apply_manifest(parsed: &mut ParsedContent, manifest: WebManifest, icon_size: u32) {
+    if (parsed.source.is_empty() || parsed.source_from_domain)
+        && let Some(name) = manifest.display_name()
+    {
+        parsed.source = name.to_string();
+        parsed.source_from_domain = false;
+    }
+    for icon in &manifest.icons {
+        if !parsed.icons.iter().any(|i| i.url == icon.url) {
+            parsed.icons.push(icon.clone());
+        }
+    }
+    if let Some(icon) = choose_icon(&parsed.icons, icon_size) {
+        parsed.favicon = icon.url.clone();
+    }
+    parsed.manifest = Some(manifest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icons::extract_icons;
+    use std::fs;
+
+    const MANIFEST: &str = r##"{
+        "name": "Somewhere  Daily",
+        "short_name": "Somewhere",
+        "theme_color": "#1a73e8",
+        "background_color": "#ffffff",
+        "start_url": "/?source=pwa",
+        "icons": [{"src": "/icons/192.png", "sizes": "192x192", "type": "image/png"}]
+    }"##;
+
+    #[test]
+    fn test_manifest_url() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        assert_eq!(
+            manifest_url(&html, "https://somewhere.com/path/story").as_deref(),
+            Some("https://somewhere.com/manifest.json")
+        );
+        assert_eq!(manifest_url("<html></html>", "https://somewhere.com/"), None);
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(MANIFEST, "https://somewhere.com/manifest.json").unwrap();
+        assert_eq!(manifest.display_name(), Some("Somewhere Daily"));
+        assert_eq!(manifest.short_name.as_deref(), Some("Somewhere"));
+        assert_eq!(manifest.theme_color.as_deref(), Some("#1a73e8"));
+        assert_eq!(manifest.background_color.as_deref(), Some("#ffffff"));
+        assert_eq!(manifest.start_url.as_deref(), Some("https://somewhere.com/?source=pwa"));
+        assert_eq!(manifest.icons[0].url, "https://somewhere.com/icons/192.png");
+
+        assert!(parse_manifest("[]", "https://somewhere.com/manifest.json").is_err());
+        assert!(parse_manifest("<html>", "https://somewhere.com/manifest.json").is_err());
+    }
+
+    #[test]
+    fn test_apply_manifest() {
+        let manifest = parse_manifest(MANIFEST, "https://somewhere.com/manifest.json").unwrap();
+        let mut parsed = ParsedContent {
+            url: "https://somewhere.com/story".to_string(),
+            source: "Somewhere".to_string(),
+            source_from_domain: true,
+            icons: extract_icons("<html></html>", "https://somewhere.com/story"),
+            ..Default::default()
+        };
+        apply_manifest(&mut parsed, manifest.clone(), 64);
+        assert_eq!(parsed.source, "Somewhere Daily");
+        assert_eq!(parsed.favicon, "https://somewhere.com/icons/192.png");
+        assert_eq!(parsed.manifest.as_ref().and_then(|m| m.theme_color.as_deref()), Some("#1a73e8"));
+
+        // A publisher name from the metadata is kept
+        let mut parsed = ParsedContent {
+            url: "https://somewhere.com/story".to_string(),
+            source: "The Somewhere Times".to_string(),
+            ..Default::default()
+        };
+        apply_manifest(&mut parsed, manifest.clone(), 64);
+        assert_eq!(parsed.source, "The Somewhere Times");
+
+        // Even when it reads like a name derived from the domain
+        let mut parsed = ParsedContent {
+            url: "https://somewhere.com/story".to_string(),
+            source: "Somewhere".to_string(),
+            ..Default::default()
+        };
+        apply_manifest(&mut parsed, manifest, 64);
+        assert_eq!(parsed.source, "Somewhere");
+    }
+}
@@ -0,0 +1,170 @@
+use crate::linker::{absolutify, is_valid_url, purify, registrable_domain};
+use ego_tree::iter::Edge;
+use scraper::{Html, Node, Selector};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A link of the extracted content
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct OutboundLink {
+    /// Absolute URL without tracking parameters or fragment
+    pub url: String,
+    /// Anchor text, whitespace collapsed
+    pub text: String,
+    /// Whether the link stays on the registrable domain of the page
+    pub internal: bool,
+    /// `rel` values of the link on the original page, lowercased
+    pub rel: Vec<String>,
+    /// Order of the link in the content, from 0
+    pub index: usize,
+    /// Characters of content text before the anchor, whitespace collapsed
+    pub offset: usize,
+}
+
+impl OutboundLink {
+    pub fn nofollow(&self) -> bool {
+        self.rel.iter().any(|r| r == "nofollow")
+    }
+
+    pub fn sponsored(&self) -> bool {
+        self.rel.iter().any(|r| r == "sponsored")
+    }
+
+    pub fn ugc(&self) -> bool {
+        self.rel.iter().any(|r| r == "ugc")
+    }
+}
+
+fn resolve(base_url: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') {
+        return None;
+    }
+    let url = absolutify(base_url, href);
+    if !is_valid_url(&url) {
+        return None;
+    }
+    Some(purify(&url).unwrap_or(url))
+}
+
+// The sanitizer drops `rel` from the content, so it is read from the original page
+fn page_rels(page_html: &str, base_url: &str) -> HashMap<String, Vec<String>> {
+    let document = Html::parse_document(page_html);
+    let selector = Selector::parse("a[href][rel]").unwrap();
+    let mut rels = HashMap::new();
+    for anchor in document.select(&selector) {
+        if let Some(url) = resolve(base_url, anchor.attr("href").unwrap_or("")) {
+            let rel: Vec<String> = anchor
+                .attr("rel")
+                .unwrap_or("")
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect();
+            rels.entry(url).or_insert(rel);
+        }
+    }
+    rels
+}
+
+/// Links of `content` in reading order, with `rel` looked up by URL in `rels`
+pub(crate) fn content_links(content: &str, base_url: &str, rels: &HashMap<String, Vec<String>>) -> Vec<OutboundLink> {
+    let fragment = Html::parse_fragment(content);
+    let site = registrable_domain(base_url);
+    let mut links: Vec<OutboundLink> = Vec::new();
+    // Open anchors as (index in links, anchor text so far)
+    let mut open: Vec<(usize, String)> = Vec::new();
+    let mut offset = 0;
+    let mut pending_space = false;
+
+    for edge in fragment.root_element().traverse() {
+        match edge {
+            Edge::Open(node) => match node.value() {
+                Node::Text(text) => {
+                    for c in text.chars() {
+                        if c.is_whitespace() {
+                            pending_space = offset > 0;
+                            continue;
+                        }
+                        if pending_space {
+                            offset += 1;
+                            pending_space = false;
+                            for (_, anchor_text) in open.iter_mut().filter(|(_, t)| !t.is_empty()) {
+                                anchor_text.push(' ');
+                            }
+                        }
+                        offset += 1;
+                        for (_, anchor_text) in open.iter_mut() {
+                            anchor_text.push(c);
+                        }
+                    }
+                }
+                Node::Element(element) if element.name() == "a" => {
+                    if let Some(url) = element.attr("href").and_then(|href| resolve(base_url, href)) {
+                        let internal = site.is_some() && registrable_domain(&url) == site;
+                        links.push(OutboundLink {
+                            rel: rels.get(&url).cloned().unwrap_or_default(),
+                            url,
+                            internal,
+                            index: links.len(),
+                            offset: offset + pending_space as usize,
+                            ..Default::default()
+                        });
+                        open.push((links.len() - 1, String::new()));
+                    }
+                }
+                _ => {}
+            },
+            Edge::Close(node) => {
+                if let Node::Element(element) = node.value()
+                    && element.name() == "a"
+                    && element.attr("href").and_then(|href| resolve(base_url, href)).is_some()
+                    && let Some((i, text)) = open.pop()
+                {
+                    links[i].text = text;
+                }
+            }
+        }
+    }
+    links
+}
+
+/// Links of the extracted content, with their `rel` read from the original page
+pub fn extract_outbound_links(content: &str, page_html: &str, base_url: &str) -> Vec<OutboundLink> {
+    content_links(content, base_url, &page_rels(page_html, base_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_outbound_links() {
+        let page = r#"<html><body><article>
+            <p>See <a href="https://partner.com/offer?utm_source=x" rel="sponsored nofollow">the offer</a></p>
+            </article><div class="comments"><a href="https://forum.example/t/1" rel="ugc">thread</a></div></body></html>"#;
+        let content = r##"<div>
+            <p>Read   <a href="/2024/05/related#top">our <b>earlier</b> story</a> and
+            <a href="https://blog.somewhere.com/post">the blog</a>.</p>
+            <p>See <a href="https://partner.com/offer?utm_source=x">the offer</a> or
+            <a href="https://forum.example/t/1">thread</a>, <a href="mailto:desk@somewhere.com">mail</a>
+            and <a href="#notes">notes</a>.</p>
+        </div>"##;
+        let links = extract_outbound_links(content, page, "https://www.somewhere.com/2024/06/story");
+        assert_eq!(links.len(), 4);
+
+        assert_eq!(links[0].url, "https://www.somewhere.com/2024/05/related");
+        assert_eq!(links[0].text, "our earlier story");
+        assert_eq!(links[0].offset, 5);
+        assert!(links[0].internal);
+
+        assert_eq!(links[1].text, "the blog");
+        assert!(links[1].internal);
+
+        assert_eq!(links[2].url, "https://partner.com/offer");
+        assert!(!links[2].internal);
+        assert!(links[2].sponsored() && links[2].nofollow() && !links[2].ugc());
+        assert_eq!(links[2].index, 2);
+
+        assert!(links[3].ugc());
+    }
+}
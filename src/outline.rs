@@ -0,0 +1,204 @@
+use ego_tree::NodeId;
+use html5ever::{local_name, ns, QualName};
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::node::Element;
+use scraper::{Html, Node, Selector};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// A heading of the extracted content
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct OutlineEntry {
+    /// 1 for `h1` through 6 for `h6`
+    pub level: u8,
+    pub text: String,
+    /// Anchor id of the heading in `ParsedContent::content`
+    pub id: String,
+}
+
+lazy_static! {
+    static ref TOC: Regex = Regex::new(r#"(?s)<nav class="toc">.*?</nav>"#).unwrap();
+}
+
+/// Turn heading text into an anchor id: lowercase alphanumerics joined by dashes
+pub fn slugify(text: &str) -> String {
+    let slug = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+fn unique_id(base: String, used: &mut HashSet<String>) -> String {
+    let mut id = base.clone();
+    let mut n = 2;
+    while used.contains(&id) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    used.insert(id.clone());
+    id
+}
+
+// Set the `id` attribute, keeping the attributes sorted as scraper looks them up
+fn set_id(element: &mut Element, id: &str) {
+    let name = QualName::new(None, ns!(), local_name!("id"));
+    match element.attrs.binary_search_by(|(attr, _)| attr.cmp(&name)) {
+        Ok(i) => element.attrs[i].1 = id.into(),
+        Err(i) => element.attrs.insert(i, (name, id.into())),
+    }
+}
+
+/// Collect the headings of `content` and give each one a unique id.
+///
+/// Existing ids are kept unless they repeat; the others are derived from the
+/// heading text, so the same content always yields the same anchors. Returns the
+/// rewritten content with the outline.
+pub fn build_outline(content: &str) -> (String, Vec<OutlineEntry>) {
+    let mut fragment = Html::parse_fragment(content);
+    let selector = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
+    let headings: Vec<(NodeId, u8, String, Option<String>)> = fragment
+        .select(&selector)
+        .map(|h| {
+            let level = h.value().name()[1..].parse().unwrap_or(1);
+            let text = h.text().collect::<Vec<_>>().join(" ");
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let id = h.attr("id").map(str::trim).filter(|id| !id.is_empty());
+            (h.id(), level, text, id.map(str::to_string))
+        })
+        .collect();
+    if headings.is_empty() {
+        return (content.to_string(), Vec::new());
+    }
+
+    // Ids already present are reserved first so generated ones never shadow them
+    let mut used = HashSet::new();
+    let reserved: HashSet<String> = headings.iter().filter_map(|(_, _, _, id)| id.clone()).collect();
+
+    let mut entries = Vec::new();
+    for (node_id, level, text, existing) in headings {
+        let id = match existing {
+            Some(id) if used.insert(id.clone()) => id,
+            _ => {
+                let mut taken = &used | &reserved;
+                let id = unique_id(slugify(&text), &mut taken);
+                used.insert(id.clone());
+                id
+            }
+        };
+        if let Some(mut node) = fragment.tree.get_mut(node_id)
+            && let Node::Element(element) = node.value()
+        {
+            set_id(element, &id);
+        }
+        entries.push(OutlineEntry { level, text, id });
+    }
+
+    (fragment.root_element().inner_html(), entries)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the outline as nested lists of anchor links in a `<nav class="toc">`
+pub fn render_toc(outline: &[OutlineEntry]) -> String {
+    if outline.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from(r#"<nav class="toc"><ul>"#);
+    // Levels of the open lists; skipped levels still open a single nested list
+    let mut open = vec![outline[0].level];
+    for (i, entry) in outline.iter().enumerate() {
+        if i > 0 {
+            if entry.level > *open.last().unwrap() {
+                html.push_str("<ul>");
+                open.push(entry.level);
+            } else {
+                html.push_str("</li>");
+                while open.len() > 1 && entry.level < *open.last().unwrap() {
+                    html.push_str("</ul></li>");
+                    open.pop();
+                }
+            }
+        }
+        html.push_str(&format!(
+            r##"<li><a href="#{}">{}</a>"##,
+            escape(&entry.id),
+            escape(&entry.text)
+        ));
+    }
+    html.push_str("</li>");
+    for _ in 1..open.len() {
+        html.push_str("</ul></li>");
+    }
+    html.push_str("</ul></nav>");
+    html
+}
+
+/// Remove a table of contents previously added by `render_toc`
+pub fn strip_toc(content: &str) -> String {
+    TOC.replace_all(content, "").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("What's next?"), "what-s-next");
+        assert_eq!(slugify("  Über   Größe "), "über-größe");
+        assert_eq!(slugify("!!!"), "section");
+    }
+
+    #[test]
+    fn test_build_outline() {
+        let content = r#"<h1>Intro</h1><p>Text</p><h2 id="setup">Setup <em>steps</em></h2><h2>Intro</h2><h3>Details</h3><h2 id="setup">Again</h2>"#;
+        let (content, outline) = build_outline(content);
+
+        assert_eq!(
+            content,
+            r#"<h1 id="intro">Intro</h1><p>Text</p><h2 id="setup">Setup <em>steps</em></h2><h2 id="intro-2">Intro</h2><h3 id="details">Details</h3><h2 id="again">Again</h2>"#
+        );
+        let ids: Vec<_> = outline.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["intro", "setup", "intro-2", "details", "again"]);
+        assert_eq!(outline[1].text, "Setup steps");
+        assert_eq!(outline[3].level, 3);
+
+        // Running it again keeps the anchors stable
+        let (again, _) = build_outline(&content);
+        assert_eq!(again, content);
+
+        // Heading-like text outside tags and ids needing escapes
+        let content = r#"<p title="<h2>">x</p><!-- <h2> --><h2 id='a"b'>First</h2><h3>Second</h3>"#;
+        let (content, outline) = build_outline(content);
+        let ids: Vec<_> = outline.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec![r#"a"b"#, "second"]);
+        assert!(content.contains(r#"<h2 id="a&quot;b">First</h2><h3 id="second">Second</h3>"#));
+    }
+
+    #[test]
+    fn test_render_and_strip_toc() {
+        let (_, outline) = build_outline("<h2>A</h2><h3>B</h3><h3>C</h3><h2>D & E</h2>");
+        let toc = render_toc(&outline);
+        assert_eq!(
+            toc,
+            r##"<nav class="toc"><ul><li><a href="#a">A</a><ul><li><a href="#b">B</a></li><li><a href="#c">C</a></li></ul></li><li><a href="#d-e">D &amp; E</a></li></ul></nav>"##
+        );
+        assert_eq!(render_toc(&[]), "");
+
+        let content = format!("{}<p>Body</p>", toc);
+        assert_eq!(strip_toc(&content), "<p>Body</p>");
+    }
+}
@@ -0,0 +1,389 @@
+use crate::blocks::parse_blocks;
+use crate::error::AppResult;
+use crate::images::ArticleImage;
+use crate::linker::{absolutify, get_domain, is_valid_url, purify};
+use crate::outbound::content_links;
+use crate::outline::{build_outline, render_toc, strip_toc};
+use crate::parse_from_html::{parse_from_html, ParseOptions, ParsedContent};
+use crate::quality::{assess_merged, QualitySignals};
+use crate::retrieve::{retrieve_html, RetrieveOptions};
+use crate::similarity::compare_two_strings;
+use crate::tables::extract_tables;
+use crate::utils::get_time_to_read;
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::{HashMap, HashSet};
+
+lazy_static! {
+    static ref NEXT_TEXT: Regex = Regex::new(
+        r"(?i)^(next|next page|older|older posts|continue|continue reading|more|[›»→>]+)\s*[›»→>]*$"
+    )
+    .unwrap();
+    static ref NEGATIVE_TEXT: Regex =
+        Regex::new(r"(?i)(prev|previous|first|last|newer|comment|reply|[«‹←<])").unwrap();
+    static ref PAGINATION_CONTAINER: Regex = Regex::new(r"(?i)(pag(e|er|ing|ination)|page-nav|next)").unwrap();
+    // A bare trailing number is an article id as often as a page, so it only
+    // counts after `/page/`; see `page_numbers` for sibling URLs
+    static ref PAGE_IN_URL: Regex =
+        Regex::new(r"(?i)(?:[?&](?:page|p|pg|paged)=(\d+))|(?:/page/(\d+)/?$)").unwrap();
+}
+
+/// Candidates scoring below this are not followed
+const MIN_NEXT_SCORE: f64 = 75.0;
+
+/// Page number found in the URL, `1` when there is none
+pub fn page_number(url: &str) -> usize {
+    PAGE_IN_URL
+        .captures(url)
+        .and_then(|c| c.get(1).or_else(|| c.get(2)))
+        .and_then(|m| m.as_str().parse().ok())
+        .filter(|n| *n < 1000)
+        .unwrap_or(1)
+}
+
+// Path without its trailing numeric segment, and that number
+fn split_trailing_number(url: &url::Url) -> (String, Option<usize>) {
+    let path = url.path().trim_end_matches('/');
+    match path.rsplit_once('/') {
+        Some((base, last)) if !last.is_empty() && last.bytes().all(|b| b.is_ascii_digit()) => {
+            (base.to_string(), last.parse().ok())
+        }
+        _ => (path.to_string(), None),
+    }
+}
+
+/// Page numbers of `url` and of a link `href` found on it.
+///
+/// Query parameters and `/page/N` are read first. A bare trailing number only
+/// counts when the two URLs differ in nothing else, as in `/story/` and
+/// `/story/2`, so `/articles/48213` is not page 48213.
+pub fn page_numbers(url: &str, href: &str) -> (usize, usize) {
+    let (current, target) = (page_number(url), page_number(href));
+    if current != 1 || target != 1 {
+        return (current, target);
+    }
+    let (Ok(a), Ok(b)) = (url::Url::parse(url), url::Url::parse(href)) else {
+        return (1, 1);
+    };
+    if a.host_str() != b.host_str() || a.query() != b.query() {
+        return (1, 1);
+    }
+    let ((base_a, number_a), (base_b, number_b)) = (split_trailing_number(&a), split_trailing_number(&b));
+    if base_a != base_b || number_a == number_b {
+        return (1, 1);
+    }
+    let page = |n: Option<usize>| n.filter(|n| *n < 1000).unwrap_or(1);
+    (page(number_a), page(number_b))
+}
+
+fn same_page(a: &str, b: &str) -> bool {
+    let key = |u: &str| purify(u).unwrap_or_else(|| u.to_string());
+    key(a).trim_end_matches('/') == key(b).trim_end_matches('/')
+}
+
+fn in_pagination_container(element: &ElementRef) -> bool {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .take(4)
+        .any(|e| {
+            let value = format!("{} {}", e.attr("class").unwrap_or(""), e.attr("id").unwrap_or(""));
+            PAGINATION_CONTAINER.is_match(&value)
+        })
+}
+
+/// Find the URL of the page following `url`.
+///
+/// `<link rel="next">` wins outright. Otherwise anchors are scored by their URL
+/// similarity to the current page, `rel=next`, "Next page"-like text, page numbers
+/// and whether they sit in a pagination container.
+pub fn find_next_page(html: &str, url: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let link_selector = Selector::parse(r#"link[rel~="next"][href]"#).unwrap();
+    if let Some(link) = document.select(&link_selector).next() {
+        let next = absolutify(url, link.attr("href")?);
+        if is_valid_url(&next) && !same_page(&next, url) {
+            return Some(next);
+        }
+    }
+
+    let host = get_domain(url)?;
+
+    let anchor_selector = Selector::parse("a[href]").unwrap();
+    // The page is number N when a sibling link says so, `/story/3/` next to `/story/2/`
+    let current = document
+        .select(&anchor_selector)
+        .filter_map(|anchor| anchor.attr("href"))
+        .map(|href| page_numbers(url, &absolutify(url, href)).0)
+        .find(|page| *page != 1)
+        .unwrap_or(1);
+    document
+        .select(&anchor_selector)
+        .filter_map(|anchor| {
+            let mut href = absolutify(url, anchor.attr("href")?);
+            if let Some(i) = href.find('#') {
+                href.truncate(i);
+            }
+            if !is_valid_url(&href) || same_page(&href, url) || get_domain(&href)? != host {
+                return None;
+            }
+            let rel_next = anchor
+                .attr("rel")
+                .is_some_and(|rel| rel.split_whitespace().any(|r| r == "next"));
+            let similarity = compare_two_strings(url, &href);
+            if similarity < 0.5 && !rel_next {
+                return None;
+            }
+
+            let text = anchor.text().collect::<String>().trim().to_string();
+            let mut score = similarity * 50.0;
+            if rel_next {
+                score += 100.0;
+            }
+            if NEXT_TEXT.is_match(&text) {
+                score += 50.0;
+            }
+            if NEGATIVE_TEXT.is_match(&text) {
+                score -= 100.0;
+            }
+            if in_pagination_container(&anchor) {
+                score += 25.0;
+            }
+            let (_, target) = page_numbers(url, &href);
+            if let Ok(number) = text.parse::<usize>() {
+                score += if number == current + 1 { 50.0 } else { -100.0 };
+            }
+            if target == current + 1 {
+                score += 25.0;
+            } else if target < current {
+                score -= 100.0;
+            }
+            Some((score, href))
+        })
+        .filter(|(score, _)| *score >= MIN_NEXT_SCORE)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, href)| href)
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Top-level blocks of a content fragment with their text, descending through
+// single wrappers so a page wrapped in one `<div>` still splits into paragraphs
+fn content_blocks(content: &str) -> Vec<(String, String)> {
+    let fragment = Html::parse_fragment(content);
+    let mut root = fragment.root_element();
+    loop {
+        let mut elements = root.children().filter_map(ElementRef::wrap);
+        let only = elements.next();
+        let has_text = root
+            .children()
+            .any(|c| c.value().as_text().is_some_and(|t| !t.trim().is_empty()));
+        match (only, elements.next()) {
+            (Some(only), None) if !has_text => root = only,
+            _ => break,
+        }
+    }
+    root.children()
+        .filter_map(|child| match child.value() {
+            Node::Text(t) => Some((t.to_string(), normalize_text(t))),
+            Node::Element(_) => ElementRef::wrap(child)
+                .map(|e| (e.html(), normalize_text(&e.text().collect::<String>()))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Append the content of subsequent pages to the first one, skipping blocks
+/// whose text already appeared on an earlier page
+pub fn merge_pages(
+    first: ParsedContent,
+    pages: Vec<ParsedContent>,
+    parser_opts: &ParseOptions,
+) -> ParsedContent {
+    let first_content = strip_toc(&first.content);
+    let mut seen: HashSet<String> = content_blocks(&first_content)
+        .into_iter()
+        .map(|(_, text)| text)
+        .filter(|text| !text.is_empty())
+        .collect();
+    let mut content = first_content;
+    let mut images = first.images.clone();
+    for page in &pages {
+        for image in &page.images {
+            if !images.iter().any(|i| i.url == image.url) {
+                images.push(ArticleImage {
+                    is_lead: false,
+                    ..image.clone()
+                });
+            }
+        }
+        for (html, text) in content_blocks(&strip_toc(&page.content)) {
+            if text.is_empty() || seen.insert(text) {
+                content.push_str(&html);
+            }
+        }
+    }
+
+    // Anchors are rebuilt over the whole article so ids stay unique across pages
+    let text = html2text::from_read(content.as_bytes(), 80).unwrap_or_default();
+    let (content, outline) = build_outline(&content);
+    let blocks = parse_blocks(&content);
+    let tables = extract_tables(&content);
+    let rels: HashMap<String, Vec<String>> = std::iter::once(&first)
+        .chain(&pages)
+        .flat_map(|page| &page.outbound_links)
+        .map(|link| (link.url.clone(), link.rel.clone()))
+        .collect();
+    let outbound_links = content_links(&content, &first.url, &rels);
+    let content = if parser_opts.inject_toc {
+        format!("{}{}", render_toc(&outline), content)
+    } else {
+        content
+    };
+    // Confidence and lengths describe the whole article, not its first page
+    let page_signals: Vec<&QualitySignals> = std::iter::once(&first)
+        .chain(&pages)
+        .map(|page| &page.quality)
+        .collect();
+    let quality = assess_merged(&content, &text, &first.title, &page_signals);
+    ParsedContent {
+        content,
+        quality,
+        outline,
+        blocks,
+        tables,
+        images,
+        outbound_links,
+        ttr: get_time_to_read(&text, parser_opts.words_per_minute),
+        page_count: first.page_count.max(1) + pages.len(),
+        ..first
+    }
+}
+
+/// Fetch and extract the pages following the first one, up to
+/// `ParseOptions::max_pages` in total, and merge them into one result.
+///
+/// Following stops at the first page that cannot be fetched or extracted,
+/// and when a next link points back to a page already visited.
+pub async fn follow_pages(
+    first: ParsedContent,
+    html: &str,
+    url: &str,
+    parser_opts: &ParseOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> AppResult<ParsedContent> {
+    let key = |u: &str| {
+        purify(u)
+            .unwrap_or_else(|| u.to_string())
+            .trim_end_matches('/')
+            .to_string()
+    };
+    let mut visited: HashSet<String> = [key(url), key(&first.url)].into_iter().collect();
+    let mut pages = Vec::new();
+    let mut current_html = html.to_string();
+    let mut current_url = url.to_string();
+
+    while 1 + pages.len() < parser_opts.max_pages {
+        let Some(next) = find_next_page(&current_html, &current_url) else {
+            break;
+        };
+        if !visited.insert(key(&next)) {
+            break;
+        }
+        let Ok(next_html) = retrieve_html(&next, fetch_opts.clone()).await else {
+            break;
+        };
+        let Ok(parsed) = parse_from_html(&next_html, &next, parser_opts).await else {
+            break;
+        };
+        pages.push(parsed);
+        current_html = next_html;
+        current_url = next;
+    }
+
+    Ok(merge_pages(first, pages, parser_opts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_number() {
+        assert_eq!(page_number("https://some.where/story"), 1);
+        assert_eq!(page_number("https://some.where/story?page=3"), 3);
+        assert_eq!(page_number("https://some.where/story?id=9&p=2"), 2);
+        assert_eq!(page_number("https://some.where/story/2/"), 1);
+        assert_eq!(page_number("https://some.where/story/page/4"), 4);
+        assert_eq!(page_number("https://some.where/2022/story"), 1);
+        assert_eq!(page_number("https://some.where/articles/48213"), 1);
+
+        assert_eq!(page_numbers("https://some.where/story/", "https://some.where/story/2/"), (1, 2));
+        assert_eq!(page_numbers("https://some.where/story/2", "https://some.where/story/3"), (2, 3));
+        assert_eq!(page_numbers("https://some.where/story/2", "https://some.where/story?page=3"), (1, 3));
+        // Another article, not another page
+        assert_eq!(page_numbers("https://some.where/articles/48213", "https://some.where/articles/48214"), (1, 1));
+        assert_eq!(page_numbers("https://some.where/articles/12", "https://some.where/other/13"), (1, 1));
+    }
+
+    #[test]
+    fn test_find_next_page_from_link() {
+        let html = r#"<html><head><link rel="next" href="/story?page=2"></head><body></body></html>"#;
+        let next = find_next_page(html, "https://some.where/story");
+        assert_eq!(next.as_deref(), Some("https://some.where/story?page=2"));
+    }
+
+    #[test]
+    fn test_find_next_page_from_anchors() {
+        let html = r#"
+            <html><body>
+                <article><p>Text</p><a href="/other-story">Next</a></article>
+                <div class="pagination">
+                    <a href="/long-read/1/">1</a>
+                    <a href="/long-read/2/">2</a>
+                    <a href="/long-read/3/">3</a>
+                    <a href="/long-read/2/">Next page »</a>
+                </div>
+                <a href="https://else.where/long-read/2/">Next</a>
+            </body></html>
+        "#;
+        let next = find_next_page(html, "https://some.where/long-read/");
+        assert_eq!(next.as_deref(), Some("https://some.where/long-read/2/"));
+
+        let next = find_next_page(html, "https://some.where/long-read/3/");
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn test_merge_pages() {
+        let page_quality = |page_text_length| QualitySignals {
+            page_text_length,
+            ..Default::default()
+        };
+        let first = ParsedContent {
+            content: "<p>Page one intro.</p><p>Shared teaser.</p>".to_string(),
+            page_count: 1,
+            quality: page_quality(60),
+            ..Default::default()
+        };
+        let second = ParsedContent {
+            content: "<div><p>Shared teaser.</p><p>Page two body.</p></div>".to_string(),
+            quality: page_quality(60),
+            ..Default::default()
+        };
+        let merged = merge_pages(first, vec![second], &ParseOptions::default());
+
+        assert_eq!(
+            merged.content,
+            "<p>Page one intro.</p><p>Shared teaser.</p><p>Page two body.</p>"
+        );
+        assert_eq!(merged.page_count, 2);
+        assert!(merged.ttr > 0);
+        // Quality covers every page
+        assert_eq!(merged.quality.paragraph_count, 3);
+        assert_eq!(merged.quality.page_text_length, 120);
+    }
+}
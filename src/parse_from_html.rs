@@ -1,16 +1,35 @@
 #![allow(unused)]
 
 use crate::error::{AppResult as Result, Error};
+use crate::amp::normalize_amp;
 use crate::normalizer::normalize;
-use crate::similarity::find_best_match;
 use crate::{
+    blocks::{parse_blocks, Block},
+    canonical::{dedupe_urls, is_amp_url, resolve_url, same_url, UrlCandidates, UrlChoice, UrlReason, UrlSource},
+    classify::{classify_page, PageClassification},
+    comments::{self, Comment},
     extract::*,
+    feed::{discover_feeds, FeedLink},
     html::{cleanify, purify},
-    linker::{absolutify, get_domain, is_valid_url, purify as purify_url},
+    icons::{choose_icon, extract_icons, SiteIcon},
+    images::{extract_images, pick_lead_image, ArticleImage},
+    linker::{absolutify, choose_best_url, get_domain, is_valid_url, purify as purify_url},
+    manifest::WebManifest,
+    outbound::{extract_outbound_links, OutboundLink},
+    outline::{build_outline, render_toc, OutlineEntry},
+    paywall::{detect_access, AccessStatus},
+    profile::find_profile,
+    psl::source_name,
+    probe::ProbeOptions,
+    quality::{assess, QualitySignals},
+    readability::ReadabilityOptions,
+    strategy::{default_strategies, run_strategy, ExtractionStrategy, StrategyInput},
+    tables::{extract_tables, DataTable},
     transformation::{exec_post_parser, exec_pre_parser},
     utils::get_time_to_read,
 };
 use reqwest::Client;
+use scraper::Html;
 
 async fn fetch_html(url: &str) -> Result<String> {
     let client = Client::new();
@@ -48,9 +67,43 @@ pub struct ParsedContent {
     pub author: String,
     pub favicon: String,
     pub source: String,
+    // Whether `source` was derived from the domain because the page names no publisher
+    pub source_from_domain: bool,
     pub published: String,
     pub ttr: usize,
     pub meta_type: String,
+    // Name of the strategy that produced the content
+    pub strategy: ExtractionStrategy,
+    // Confidence score and the signals it is derived from
+    pub quality: QualitySignals,
+    // Whether the content is the article or a paywall/consent wall
+    pub access: AccessStatus,
+    // Number of pages stitched into the content
+    pub page_count: usize,
+    // User comments, filled when `ParseOptions::extract_comments` is set
+    pub comments: Vec<Comment>,
+    // Headings of the content with their anchor ids
+    pub outline: Vec<OutlineEntry>,
+    // Typed block tree of the content, see `blocks::to_markdown` and friends
+    pub blocks: Vec<Block>,
+    // Data tables of the content, exportable as CSV or JSON
+    pub tables: Vec<DataTable>,
+    // Lead image and every image of the content
+    pub images: Vec<ArticleImage>,
+    // Every icon the site declares, `favicon` is the best fit among them
+    pub icons: Vec<SiteIcon>,
+    // Web app manifest of the site, filled when `ParseOptions::fetch_manifest` is set
+    pub manifest: Option<WebManifest>,
+    // RSS and Atom feeds advertised by the page
+    pub feeds: Vec<FeedLink>,
+    // Article, listing, home page... with the signals behind it
+    pub page_type: PageClassification,
+    // Links of the content, unlike `links` which are URLs of the page itself
+    pub outbound_links: Vec<OutboundLink>,
+    // Which candidate became `url` and why the others were rejected
+    pub url_choice: UrlChoice,
+    // AMP page the content was read from, when `ParseOptions::amp_fallback` replaced a poor extraction
+    pub amp_url: Option<String>,
 }
 
 #[derive(Debug)]
@@ -59,6 +112,37 @@ pub struct ParseOptions {
     pub desc_truncate_len: usize,
     pub desc_len_threshold: usize,
     pub content_len_threshold: usize,
+    pub readability: ReadabilityOptions,
+    /// Content extraction strategies, tried in order
+    pub strategies: Vec<ExtractionStrategy>,
+    /// Fail with `RestrictedContent` instead of returning a paywall teaser
+    /// or a consent wall as the article
+    pub reject_restricted: bool,
+    /// Fail with `NotArticle` before extraction when the page is classified as
+    /// a listing, home page or anything else than an article
+    pub reject_non_articles: bool,
+    /// Follow `rel=next` pagination and stitch the pages into one article
+    /// (`extract_from_url` only)
+    pub follow_pagination: bool,
+    /// Maximum number of pages fetched when following pagination, first page included
+    pub max_pages: usize,
+    /// Collect user comments into `ParsedContent::comments`
+    pub extract_comments: bool,
+    /// Prepend a table of contents built from the outline to the content
+    pub inject_toc: bool,
+    /// Read the dimensions of images that declare none from the first bytes of
+    /// each file (`extract_from_url` only)
+    pub probe_images: Option<ProbeOptions>,
+    /// Size in pixels the `favicon` is picked for among the site icons
+    pub icon_size: u32,
+    /// Fetch the `<link rel="manifest">` of the page for the site name, colors
+    /// and icons (`extract_from_url` only)
+    pub fetch_manifest: bool,
+    /// Extract the `<link rel="amphtml">` version of the page instead when the
+    /// extraction fails or its confidence is below `amp_confidence`
+    /// (`extract_from_url` only)
+    pub amp_fallback: bool,
+    pub amp_confidence: f64,
 }
 
 impl ParseOptions {
@@ -73,6 +157,19 @@ impl ParseOptions {
             desc_len_threshold,
             desc_truncate_len,
             content_len_threshold,
+            readability: ReadabilityOptions::default(),
+            strategies: default_strategies(),
+            reject_restricted: false,
+            reject_non_articles: false,
+            follow_pagination: false,
+            max_pages: 10,
+            extract_comments: false,
+            inject_toc: false,
+            probe_images: None,
+            icon_size: 64,
+            fetch_manifest: false,
+            amp_fallback: false,
+            amp_confidence: 0.5,
         }
     }
 }
@@ -84,6 +181,19 @@ impl Default for ParseOptions {
             desc_truncate_len: 210,
             desc_len_threshold: 180,
             content_len_threshold: 200,
+            readability: ReadabilityOptions::default(),
+            strategies: default_strategies(),
+            reject_restricted: false,
+            reject_non_articles: false,
+            follow_pagination: false,
+            max_pages: 10,
+            extract_comments: false,
+            inject_toc: false,
+            probe_images: None,
+            icon_size: 64,
+            fetch_manifest: false,
+            amp_fallback: false,
+            amp_confidence: 0.5,
         }
     }
 }
@@ -94,7 +204,9 @@ pub async fn parse_from_html(
     parsed_options: &ParseOptions,
 ) -> Result<ParsedContent> {
     // Extract metadata from original HTML before purification — ammonia strips head/meta/link tags
-    let meta = extract_metadata(input_html);
+    // One parse of the page serves the metadata and the title fallback
+    let document = Html::parse_document(input_html);
+    let meta = extract_metadata_from_document(&document);
     let pure_html = purify(input_html);
 
     let MetaEntry {
@@ -116,21 +228,25 @@ pub async fn parse_from_html(
         desc_truncate_len,
         desc_len_threshold,
         content_len_threshold,
+        ref readability,
+        ref strategies,
+        reject_restricted,
+        reject_non_articles,
+        extract_comments,
+        inject_toc,
+        icon_size,
+        ..
     } = parsed_options;
-    let mut title = title;
-    if title.is_empty() {
-        // Use original HTML instead of purified HTML for readability extraction
-        // because purify() strips too much content and breaks readability
-        let err_msg = format!("Unable to extract title with readability!");
-        match extract_title_with_readability(&input_html, input_url) {
-            Some(t) => title = t,
-            _ => return Err(Error::AppError(err_msg)),
-        };
-    }
-
     // TODO: FixME: Look for unique
     // Use set to discard duplicates
-    let links: Vec<String> = vec![url, shortlink, amphtml, canonical, input_url.to_string()]
+    let candidates = UrlCandidates {
+        canonical: &canonical,
+        og_url: &url,
+        input: input_url,
+        amphtml: &amphtml,
+        shortlink: &shortlink,
+    };
+    let links: Vec<String> = vec![url.clone(), shortlink.clone(), amphtml.clone(), canonical.clone(), input_url.to_string()]
         .into_iter()
         .filter(|u| is_valid_url(&u))
         .map(|url| purify_url(&url).unwrap_or(url))
@@ -140,59 +256,171 @@ pub async fn parse_from_html(
         return Err(Error::NullError(format!("Links")));
     }
 
-    let best_url = choose_best_url(&links, &title)?;
+    // Site profile selectors take precedence over the generic extraction, field by field
+    let profile = find_profile(&links);
+    let fields = profile
+        .as_ref()
+        .map(|p| p.extract(input_html))
+        .unwrap_or_default();
+
+    let mut title = fields.title.unwrap_or(title);
+    if title.is_empty() {
+        // Use original HTML instead of purified HTML for readability extraction
+        // because purify() strips too much content and breaks readability
+        let err_msg = format!("Unable to extract title with readability!");
+        match extract_title_from_document(&document, input_url) {
+            Some(t) => title = t,
+            _ => return Err(Error::AppError(err_msg)),
+        };
+    }
+    let author = fields.author.unwrap_or(author);
+    let published = fields.published.unwrap_or(published);
+    let image = fields.image.unwrap_or(image);
+
+    let url_choice = resolve_url(&candidates, &title);
+    let best_url = if url_choice.url.is_empty() {
+        // Variants of the same URL (www, mobile...) only compete once, and
+        // shortlinks and AMP URLs not at all
+        let excluded: Vec<&str> = url_choice
+            .rejected
+            .iter()
+            .filter(|(_, _, reason)| matches!(reason, UrlReason::Shortlink | UrlReason::Amp))
+            .map(|(url, _, _)| url.as_str())
+            .collect();
+        let remaining: Vec<String> = dedupe_urls(&links)
+            .into_iter()
+            .filter(|url| !is_amp_url(url) && !excluded.iter().any(|e| same_url(e, url)))
+            .collect();
+        choose_best_url(remaining, &title).ok_or(Error::NullError("URL".to_string()))?
+    } else {
+        url_choice.url.clone()
+    };
 
-    // Derive source from URL hostname when not provided in metadata
-    let source = if source.is_empty() {
-        get_domain(&best_url).unwrap_or_default()
+    let page_type = classify_page(input_html, &best_url);
+    if reject_non_articles && !page_type.is_article() {
+        return Err(Error::NotArticle(page_type.page_type));
+    }
+
+    // Derive a publisher name from the registrable domain when not provided in metadata
+    let source_from_domain = source.is_empty();
+    let source = if source_from_domain {
+        source_name(&best_url)
     } else {
         source
     };
 
+    let comments = if extract_comments {
+        comments::extract_comments(input_html)
+    } else {
+        Vec::new()
+    };
+
     // Start the sequence of operation to extract the content
+    // AMP components are custom elements the sanitizer would strip
+    let input_html = if input_html.contains("<amp-") {
+        normalize_amp(input_html)
+    } else {
+        input_html.to_string()
+    };
     let input_html = normalize(&input_html, &best_url)?;
     
     let content = exec_pre_parser(&input_html, &links);
-    let content = extract_with_readability(&content, &best_url)
-        .ok_or(Error::NullError(format!("Content")))?;
-    // Re-normalize after readability to restore target="_blank" on links (readability strips it)
-    let content = normalize(&content, &best_url).unwrap_or(content);
-    let content = exec_post_parser(&content, &links)
-        .map(|c| cleanify(&c))
-        .unwrap_or_else(|| cleanify(&content));
-
-    let text_content = strip_tags(&content)?;
-
-    if text_content.len() < content_len_threshold {
-        return Err(Error::NullError(format!("Text content")));
+    // Strip before running the strategies so none of them sees the junk
+    let content = match &profile {
+        Some(profile) => profile.strip(&content),
+        None => content,
+    };
+    let input = StrategyInput {
+        html: &content,
+        url: &best_url,
+        profile: profile.as_ref(),
+        description: &meta.description,
+        readability,
+    };
+
+    // Try each strategy in turn, the first candidate with enough text wins
+    let mut extracted = None;
+    for &strategy in strategies {
+        let Some(candidate) = run_strategy(strategy, &input) else {
+            continue;
+        };
+        // Re-normalize after extraction to restore target="_blank" on links
+        let candidate = normalize(&candidate, &best_url).unwrap_or(candidate);
+        let candidate = exec_post_parser(&candidate, &links)
+            .map(|c| cleanify(&c))
+            .unwrap_or_else(|| cleanify(&candidate));
+        let text_content = strip_tags(&candidate)?;
+        if text_content.len() >= strategy.min_text_length(content_len_threshold) {
+            extracted = Some((strategy, candidate, text_content));
+            break;
+        }
+    }
+    let (strategy, content, text_content) =
+        extracted.ok_or(Error::NullError("Content".to_string()))?;
+
+    let access = detect_access(&input_html, &text_content);
+    if reject_restricted && access != AccessStatus::Open {
+        return Err(Error::RestrictedContent(access));
     }
 
+    let quality = assess(&content, &text_content, &input_html, &title, access);
+    let (content, outline) = build_outline(&content);
+    let blocks = parse_blocks(&content);
+    let tables = extract_tables(&content);
+    let content = if inject_toc {
+        format!("{}{}", render_toc(&outline), content)
+    } else {
+        content
+    };
     let description = summarize(&meta.description, &text_content, 180, 210);
-    let image = absolutify(&best_url, &image);
-    let favicon = absolutify(&best_url, &favicon);
+    // Fall back to the most likely hero image when the metadata names none
+    let image = if image.trim().is_empty() {
+        pick_lead_image(&input_html, &content, &best_url).unwrap_or_default()
+    } else {
+        absolutify(&best_url, &image)
+    };
+    let images = extract_images(&content, &input_html, &best_url, &image);
+    let outbound_links = extract_outbound_links(&content, &input_html, &best_url);
+    let icons = extract_icons(&input_html, &best_url);
+    let feeds = discover_feeds(&input_html, &best_url);
+    let favicon = choose_icon(&icons, icon_size)
+        .map(|icon| icon.url.clone())
+        .unwrap_or_else(|| absolutify(&best_url, &favicon));
     let parsed_content = ParsedContent {
         url: best_url,
         title,
         description,
         links,
         content,
-        author: meta.author,
-        published: meta.published,
+        author,
+        published,
         image,
         favicon,
         source,
+        source_from_domain,
         ttr: get_time_to_read(&text_content, words_per_minute),
         meta_type,
+        strategy,
+        quality,
+        access,
+        page_count: 1,
+        comments,
+        outline,
+        blocks,
+        tables,
+        images,
+        icons,
+        manifest: None,
+        feeds,
+        page_type,
+        outbound_links,
+        url_choice,
+        amp_url: None,
     };
 
     Ok(parsed_content)
 }
 
-fn choose_best_url(candidates: &Vec<String>, title: &str) -> Result<String> {
-    let ranking = find_best_match(title, candidates)?;
-    Ok(ranking.best_match.target)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,7 +465,10 @@ mod tests {
                     html: fs::read_to_string("./test-data/html-no-article.html").unwrap(),
                     url: None,
                 },
-                expectation: None,
+                // Only the meta description is left
+                expectation: Some(|result| {
+                    assert_eq!(result.strategy, ExtractionStrategy::MetaDescription);
+                }),
             },
             TestCase {
                 input: Input {
@@ -254,7 +485,8 @@ mod tests {
                     url: None,
                 },
                 expectation: Some(|result| {
-                    assert_eq!(result.source, "somewhere.any");
+                    assert_eq!(result.source, "Somewhere");
+                    assert!(result.source_from_domain);
                 }),
             },
             TestCase {
@@ -323,4 +555,116 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_parser_with_site_profile() {
+        use crate::profile::{add_profiles, SiteProfile};
+        use regex::Regex;
+
+        add_profiles(vec![SiteProfile {
+            patterns: vec![Regex::new(r"https://profiled\.tld/.*").unwrap()],
+            title: Some("section > h1".to_string()),
+            author: Some("nav".to_string()),
+            published: Some("time".to_string()),
+            content: Some("article".to_string()),
+            strip: vec!["div.contentdetail".to_string(), "address".to_string()],
+            ..Default::default()
+        }]);
+
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let parsed_options = ParseOptions {
+            content_len_threshold: 50,
+            ..Default::default()
+        };
+        let result = parse_from_html(&html, "https://profiled.tld/article", &parsed_options)
+            .await
+            .unwrap();
+
+        assert_eq!(result.title, "Article title here");
+        assert_eq!(result.author, "Navigation here");
+        // No <time> element, fall back to the meta tags
+        assert_eq!(result.published, "2021-12-16T09:00:00.000+07:00");
+        assert!(result.content.contains("Those cheetahs are nothing more than dogs"));
+        assert!(!result.content.contains("rational peach"));
+        assert!(!result.content.contains("Kelly Drive"));
+    }
+
+    #[tokio::test]
+    async fn test_parser_strategy_chain() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let parsed_options = ParseOptions::default();
+        let result = parse_from_html(&html, "", &parsed_options).await.unwrap();
+        assert_eq!(result.strategy, ExtractionStrategy::Readability);
+        assert_eq!(result.quality.paragraph_count, 2);
+        assert!(result.quality.confidence > 0.5);
+        // The shortlink and AMP URL never win over the canonical one
+        assert_eq!(result.url, "https://somewhere.com/another/path/to/article-title-here");
+        assert_eq!(result.url_choice.source, UrlSource::Canonical);
+
+        // Nothing in the body, only the meta description is left
+        let html = fs::read_to_string("./test-data/html-no-article.html").unwrap();
+        let parsed_options = ParseOptions::default();
+        let result = parse_from_html(&html, "", &parsed_options).await.unwrap();
+        assert_eq!(result.strategy, ExtractionStrategy::MetaDescription);
+        assert_eq!(result.content, "<p>Few words about this article</p>");
+
+        let parsed_options = ParseOptions {
+            strategies: vec![ExtractionStrategy::Readability, ExtractionStrategy::Semantic],
+            ..Default::default()
+        };
+        assert!(parse_from_html(&html, "", &parsed_options).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parser_shortlink_input() {
+        let article = "<p>Stocks closed higher on Friday as investors weighed fresh inflation data from the labor department, \
+            with the index gaining for a third straight session after a volatile week of trading on Wall Street.</p>";
+        let html = format!(
+            r#"<html><head><title>Markets close higher</title>
+            <link rel="shortlink" href="https://sw.re/419283"></head>
+            <body><article>{}</article></body></html>"#,
+            article
+        );
+        // Nothing but the shortlink is known about the page
+        let result = parse_from_html(&html, "https://sw.re/419283", &ParseOptions::default()).await;
+        assert!(matches!(result, Err(Error::NullError(_))));
+
+        let html = html.replace(
+            "</head>",
+            r#"<link rel="amphtml" href="https://news.tld/amp/markets-close-higher"></head>"#,
+        );
+        let result = parse_from_html(&html, "https://sw.re/419283", &ParseOptions::default()).await.unwrap();
+        assert_eq!(result.url, "https://news.tld/markets-close-higher");
+    }
+
+    #[tokio::test]
+    async fn test_parser_restricted_access() {
+        let html = r#"
+            <html><head>
+                <title>Markets close higher</title>
+                <meta property="og:url" content="https://news.tld/markets-close-higher">
+                <script type="application/ld+json">{"@type": "NewsArticle", "isAccessibleForFree": false}</script>
+            </head><body><article>
+                <p>Stocks closed higher on Friday as investors weighed fresh inflation data from the labor department.</p>
+                <p>Subscribe to continue reading this article.</p>
+            </article></body></html>
+        "#;
+        let parsed_options = ParseOptions {
+            content_len_threshold: 50,
+            ..Default::default()
+        };
+        let result = parse_from_html(html, "", &parsed_options).await.unwrap();
+        assert_eq!(result.access, AccessStatus::Paywalled);
+
+        let parsed_options = ParseOptions {
+            content_len_threshold: 50,
+            reject_restricted: true,
+            ..Default::default()
+        };
+        let result = parse_from_html(html, "", &parsed_options).await;
+        assert!(matches!(
+            result,
+            Err(Error::RestrictedContent(AccessStatus::Paywalled))
+        ));
+    }
 }
@@ -0,0 +1,272 @@
+use crate::extract::ld_json_objects;
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Whether the extracted content is the article or a wall in front of it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessStatus {
+    #[default]
+    Open,
+    /// A paywall teaser instead of the full article
+    Paywalled,
+    /// A cookie or GDPR consent interstitial
+    ConsentWall,
+}
+
+impl fmt::Display for AccessStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Open => f.write_str("open"),
+            Self::Paywalled => f.write_str("paywall"),
+            Self::ConsentWall => f.write_str("consent wall"),
+        }
+    }
+}
+
+// Class and id tokens of paywall containers; `no-paywall` and script text do not count
+const PAYWALL_MARKUP: &str = r"(?i)^(paywall|regwall|subscriber-only|subscribers-only|premium-content|meteredcontent|tp-modal|piano-offer)([-_].*)?$";
+
+// Consent management platforms: OneTrust, Cookiebot, Quantcast, Didomi, Sourcepoint,
+// TrustArc, Google Funding Choices and Usercentrics
+const CONSENT_SELECTORS: &str = "#onetrust-consent-sdk, #onetrust-banner-sdk, #CybotCookiebotDialog, .qc-cmp2-container, #qc-cmp2-ui, #didomi-host, [id^=sp_message_container], #truste-consent-track, .fc-consent-root, #usercentrics-root, [class*=cookie-consent], [id*=cookie-consent]";
+
+const TEASER_PHRASES: &[&str] = &[
+    "subscribe to continue",
+    "subscribe to read",
+    "to continue reading",
+    "continue reading with a subscription",
+    "subscribers only",
+    "for subscribers",
+    "already a subscriber",
+    "sign in to read",
+    "log in to continue",
+    "become a member to read",
+    "unlock this article",
+];
+
+const CONSENT_PHRASES: &[&str] = &[
+    "cookie",
+    "consent",
+    "accept all",
+    "reject all",
+    "manage preferences",
+    "privacy settings",
+    "partners",
+    "legitimate interest",
+];
+
+/// Extracted text below this many words counts as a teaser
+const TEASER_MAX_WORDS: usize = 150;
+
+lazy_static! {
+    static ref PAYWALL_MARKUP_RE: Regex = Regex::new(PAYWALL_MARKUP).unwrap();
+}
+
+/// Whether the page HTML carries common paywall markup: an element whose class
+/// or id names a paywall container
+pub fn has_paywall_markup(html: &str) -> bool {
+    has_paywall_elements(&Html::parse_document(html))
+}
+
+fn has_paywall_elements(document: &Html) -> bool {
+    let selector = Selector::parse("[class], [id]").unwrap();
+    document.select(&selector).any(|element| {
+        element
+            .value()
+            .classes()
+            .chain(element.value().id())
+            .any(|token| PAYWALL_MARKUP_RE.is_match(token))
+    })
+}
+
+fn is_false(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => !b,
+        Value::String(s) => s.eq_ignore_ascii_case("false"),
+        _ => false,
+    }
+}
+
+// `cssSelector` of the gated parts of a `hasPart` value
+fn gated_selectors(part: &Value) -> Vec<String> {
+    match part {
+        Value::Array(parts) => parts.iter().flat_map(gated_selectors).collect(),
+        part if part.get("isAccessibleForFree").is_some_and(is_false) => part
+            .get("cssSelector")
+            .and_then(Value::as_str)
+            .map(|selector| vec![selector.to_string()])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Gated sections the JSON-LD of the page declares, `None` when it declares the
+/// article free or says nothing.
+///
+/// Publishers following Google's guidelines pair `isAccessibleForFree: false` with
+/// `hasPart.cssSelector` pointing at the gated section. The selector list is empty
+/// when only the top-level flag is set.
+pub fn declared_paywall(document: &Html) -> Option<Vec<String>> {
+    let mut declared = None;
+    for object in ld_json_objects(document) {
+        let selectors = object.get("hasPart").map(gated_selectors).unwrap_or_default();
+        if object.get("isAccessibleForFree").is_some_and(is_false) || !selectors.is_empty() {
+            declared.get_or_insert_with(Vec::new).extend(selectors);
+        }
+    }
+    declared
+}
+
+/// Whether the JSON-LD of the page declares the article as not free
+pub fn is_declared_paywalled(document: &Html) -> bool {
+    declared_paywall(document).is_some()
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Whether a gated section of the page is missing from the extracted text: the
+// page does not serve it, or serves it empty, or extraction lost it
+fn section_missing(document: &Html, selector: &str, text: &str) -> bool {
+    let Ok(selector) = Selector::parse(selector) else {
+        return false;
+    };
+    let section: String = document
+        .select(&selector)
+        .map(|element| collapse(&element.text().collect::<String>()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let head: String = section.chars().take(80).collect();
+    head.split_whitespace().count() < 3 || !text.contains(&head)
+}
+
+fn has_consent_markup(document: &Html) -> bool {
+    let selector = Selector::parse(CONSENT_SELECTORS).unwrap();
+    document.select(&selector).next().is_some()
+}
+
+fn count_phrases(text: &str, phrases: &[&str]) -> usize {
+    phrases.iter().filter(|p| text.contains(*p)).count()
+}
+
+/// Classify the extracted text of a page as open, paywalled or a consent wall.
+///
+/// `page_html` is the whole page and `text` the plain text that was extracted from it.
+pub fn detect_access(page_html: &str, text: &str) -> AccessStatus {
+    let document = Html::parse_document(page_html);
+    let lowered = text.to_lowercase();
+    let words = text.split_whitespace().count();
+    let short = words < TEASER_MAX_WORDS;
+
+    // Consent walls replace the whole page, so their text is mostly about cookies
+    let consent_hits = count_phrases(&lowered, CONSENT_PHRASES);
+    if short && consent_hits >= 2 && (has_consent_markup(&document) || consent_hits >= 3) {
+        return AccessStatus::ConsentWall;
+    }
+
+    // Publishers keep the flag on pages served in full to subscribers and crawlers
+    if let Some(selectors) = declared_paywall(&document) {
+        let collapsed = collapse(text);
+        if short || selectors.iter().any(|selector| section_missing(&document, selector, &collapsed)) {
+            return AccessStatus::Paywalled;
+        }
+    }
+    let teaser = count_phrases(&lowered, TEASER_PHRASES) > 0;
+    if short && (teaser || has_paywall_elements(&document)) {
+        return AccessStatus::Paywalled;
+    }
+    AccessStatus::Open
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_open_article() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let text = "Few can name a rational peach that isn't a conscientious goldfish!";
+        assert_eq!(detect_access(&html, text), AccessStatus::Open);
+    }
+
+    #[test]
+    fn test_json_ld_paywall() {
+        let html = r#"
+            <html><head><script type="application/ld+json">
+            {
+                "@context": "https://schema.org",
+                "@type": "NewsArticle",
+                "isAccessibleForFree": "False",
+                "hasPart": {
+                    "@type": "WebPageElement",
+                    "isAccessibleForFree": "False",
+                    "cssSelector": ".paywalled-section"
+                }
+            }
+            </script></head><body><p>Opening paragraph.</p></body></html>
+        "#;
+        assert_eq!(detect_access(html, "Opening paragraph."), AccessStatus::Paywalled);
+    }
+
+    #[test]
+    fn test_json_ld_flag_full_text() {
+        let paragraph = "The full story goes on at length about the city budget and its consequences. ";
+        let body = paragraph.repeat(20);
+        let html = format!(
+            r#"<html><head><script type="application/ld+json">
+            {{
+                "@type": "NewsArticle",
+                "isAccessibleForFree": false,
+                "hasPart": {{"@type": "WebPageElement", "isAccessibleForFree": false, "cssSelector": ".gated"}}
+            }}
+            </script></head><body><p>Opening paragraph.</p><div class="gated"><p>{}</p></div></body></html>"#,
+            body
+        );
+        let text = format!("Opening paragraph. {}", body);
+        assert_eq!(detect_access(&html, &text), AccessStatus::Open);
+        // Same page served without the gated section
+        let teaser_page = html.replace(&body, "");
+        let text = format!("Opening paragraph. {}", paragraph.repeat(19));
+        assert_eq!(detect_access(&teaser_page, &text), AccessStatus::Paywalled);
+
+        // Top-level flag alone, full text
+        let html = format!(
+            r#"<html><head><script type="application/ld+json">{{"@type": "NewsArticle", "isAccessibleForFree": "False"}}</script>
+            </head><body><p>{}</p></body></html>"#,
+            body
+        );
+        assert_eq!(detect_access(&html, &body), AccessStatus::Open);
+    }
+
+    #[test]
+    fn test_teaser_paywall() {
+        let html = r#"<html><body><article><p>The first lines of the story.</p>
+            <div class="gate">Subscribe to continue reading.</div></article></body></html>"#;
+        let text = "The first lines of the story. Subscribe to continue reading.";
+        assert_eq!(detect_access(html, text), AccessStatus::Paywalled);
+
+        let html = r#"<html><body><div class="paywall"></div><p>The first lines.</p></body></html>"#;
+        assert_eq!(detect_access(html, "The first lines."), AccessStatus::Paywalled);
+
+        // The word alone, in scripts or negated class names, is no paywall
+        let html = r#"<html><body><div class="no-paywall"><p>A brief.</p></div>
+            <script>window.paywall = {enabled: false};</script></body></html>"#;
+        assert_eq!(detect_access(html, "A brief."), AccessStatus::Open);
+    }
+
+    #[test]
+    fn test_consent_wall() {
+        let html = r#"<html><body><div id="onetrust-consent-sdk">
+            <p>We and our partners use cookies. Accept all or manage preferences.</p>
+            </div></body></html>"#;
+        let text = "We and our partners use cookies. Accept all or manage preferences.";
+        assert_eq!(detect_access(html, text), AccessStatus::ConsentWall);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::images::ArticleImage;
+use crate::retrieve::{retrieve_prefix, RetrieveOptions};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Image formats recognized from their first bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Avif,
+}
+
+/// Format and pixel dimensions read from an image header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Limits of a probing run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProbeOptions {
+    /// Bytes fetched per image; large EXIF blocks may push JPEG dimensions past it
+    pub max_bytes_per_image: usize,
+    /// Bytes fetched over the whole run, images past the budget are skipped
+    pub max_total_bytes: usize,
+    /// Images fetched at the same time
+    pub concurrency: usize,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_image: 16 * 1024,
+            max_total_bytes: 1024 * 1024,
+            concurrency: 4,
+        }
+    }
+}
+
+/// Entries kept in the probe cache; it is emptied when full so long crawls stay bounded
+const PROBE_CACHE_LIMIT: usize = 4096;
+
+lazy_static! {
+    // Images whose bytes could not be read as a header are cached as `None` so
+    // they are not fetched again; network and HTTP failures are not cached
+    static ref PROBE_CACHE: Mutex<HashMap<String, Option<ImageInfo>>> = Mutex::new(HashMap::new());
+}
+
+type HeaderParser = fn(&[u8]) -> Option<(u32, u32)>;
+
+fn be16(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 2)?;
+    Some(u16::from_be_bytes([b[0], b[1]]) as u32)
+}
+
+fn le16(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]) as u32)
+}
+
+fn le24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn png(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") || bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be32(bytes, 16)?, be32(bytes, 20)?))
+}
+
+fn gif(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(b"GIF87a") && !bytes.starts_with(b"GIF89a") {
+        return None;
+    }
+    Some((le16(bytes, 6)?, le16(bytes, 8)?))
+}
+
+// Walk the JPEG segments up to the first start-of-frame marker
+fn jpeg(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut i = 2;
+    loop {
+        while *bytes.get(i)? != 0xFF {
+            i += 1;
+        }
+        while *bytes.get(i)? == 0xFF {
+            i += 1;
+        }
+        let marker = *bytes.get(i)?;
+        i += 1;
+        match marker {
+            0xD0..=0xD9 | 0x01 => continue,
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((be16(bytes, i + 5)?, be16(bytes, i + 3)?));
+            }
+            _ => i += be16(bytes, i)? as usize,
+        }
+    }
+}
+
+fn webp(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    match bytes.get(12..16)? {
+        b"VP8 " => Some((le16(bytes, 26)? & 0x3FFF, le16(bytes, 28)? & 0x3FFF)),
+        b"VP8L" => {
+            let b = bytes.get(21..25)?;
+            let width = 1 + (((b[1] as u32 & 0x3F) << 8) | b[0] as u32);
+            let height =
+                1 + (((b[3] as u32 & 0x0F) << 10) | (b[2] as u32) << 2 | (b[1] as u32 & 0xC0) >> 6);
+            Some((width, height))
+        }
+        b"VP8X" => Some((1 + le24(bytes, 24)?, 1 + le24(bytes, 27)?)),
+        _ => None,
+    }
+}
+
+// AVIF is an ISO-BMFF file; the `ispe` property holds the dimensions. The largest
+// one is kept since thumbnails and grid tiles come with their own.
+fn avif(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.get(4..8)? != b"ftyp" {
+        return None;
+    }
+    let ftyp_len = (be32(bytes, 0)? as usize).min(bytes.len());
+    let brands = bytes.get(8..ftyp_len)?;
+    if !brands.chunks(4).any(|b| b == b"avif" || b == b"avis") {
+        return None;
+    }
+    bytes
+        .windows(4)
+        .enumerate()
+        .filter(|(_, w)| *w == b"ispe")
+        .filter_map(|(at, _)| Some((be32(bytes, at + 8)?, be32(bytes, at + 12)?)))
+        .max_by_key(|(w, h)| *w as u64 * *h as u64)
+}
+
+/// Read the format and dimensions from the first bytes of an image
+pub fn parse_image_header(bytes: &[u8]) -> Option<ImageInfo> {
+    let parsers: [(ImageFormat, HeaderParser); 5] = [
+        (ImageFormat::Png, png),
+        (ImageFormat::Gif, gif),
+        (ImageFormat::Jpeg, jpeg),
+        (ImageFormat::Webp, webp),
+        (ImageFormat::Avif, avif),
+    ];
+    parsers.iter().find_map(|(format, parse)| {
+        let (width, height) = parse(bytes)?;
+        (width > 0 && height > 0).then_some(ImageInfo {
+            format: *format,
+            width,
+            height,
+        })
+    })
+}
+
+fn cached(url: &str) -> Option<Option<ImageInfo>> {
+    PROBE_CACHE.lock().unwrap().get(url).copied()
+}
+
+fn store(url: &str, info: Option<ImageInfo>) {
+    let mut cache = PROBE_CACHE.lock().unwrap();
+    if cache.len() >= PROBE_CACHE_LIMIT && !cache.contains_key(url) {
+        cache.clear();
+    }
+    cache.insert(url.to_string(), info);
+}
+
+/// Fetch the first `max_bytes` of an image and read its header, using the cache
+pub async fn probe_image(
+    url: &str,
+    max_bytes: usize,
+    fetch_opts: Option<RetrieveOptions>,
+) -> Option<ImageInfo> {
+    if let Some(info) = cached(url) {
+        return info;
+    }
+    // A timeout or a 5xx may not happen next time, so only read bytes are cached
+    let bytes = retrieve_prefix(url, max_bytes, fetch_opts).await.ok()?;
+    let info = parse_image_header(&bytes);
+    store(url, info);
+    info
+}
+
+/// Probe several images concurrently within the limits of `options`.
+///
+/// Only images that could be read are in the result. Cached URLs do not count
+/// against the byte budget.
+pub async fn probe_images(
+    urls: &[String],
+    options: &ProbeOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> HashMap<String, ImageInfo> {
+    let mut results = HashMap::new();
+    let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut budget = options.max_total_bytes;
+    let per_image = options.max_bytes_per_image;
+    let mut tasks = JoinSet::new();
+
+    let mut seen = Vec::new();
+    for url in urls {
+        if seen.contains(url) {
+            continue;
+        }
+        seen.push(url.clone());
+        if let Some(info) = cached(url) {
+            if let Some(info) = info {
+                results.insert(url.clone(), info);
+            }
+            continue;
+        }
+        // Every fetch may read up to `per_image`, so that much is taken from the budget
+        let Some(left) = budget.checked_sub(per_image) else {
+            break;
+        };
+        budget = left;
+
+        let url = url.clone();
+        let semaphore = semaphore.clone();
+        let fetch_opts = fetch_opts.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            let info = probe_image(&url, per_image, fetch_opts).await?;
+            Some((url, info))
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        if let Ok(Some((url, info))) = result {
+            results.insert(url, info);
+        }
+    }
+    results
+}
+
+/// Fill in the missing dimensions of article images by probing them
+pub async fn fill_dimensions(
+    images: &mut [ArticleImage],
+    options: &ProbeOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) {
+    let urls: Vec<String> = images
+        .iter()
+        .filter(|image| image.width.is_none() || image.height.is_none())
+        .map(|image| image.url.clone())
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+    let infos = probe_images(&urls, options, fetch_opts).await;
+    for image in images.iter_mut() {
+        if let Some(info) = infos.get(&image.url) {
+            image.width = Some(info.width);
+            image.height = Some(info.height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_and_gif() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&1200u32.to_be_bytes());
+        png.extend_from_slice(&630u32.to_be_bytes());
+        assert_eq!(
+            parse_image_header(&png),
+            Some(ImageInfo { format: ImageFormat::Png, width: 1200, height: 630 })
+        );
+
+        let gif = b"GIF89a\x10\x00\x10\x00\x80\x00\x00";
+        assert_eq!(
+            parse_image_header(gif),
+            Some(ImageInfo { format: ImageFormat::Gif, width: 16, height: 16 })
+        );
+    }
+
+    #[test]
+    fn test_jpeg() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // APP0 segment to skip, then a baseline SOF0 of 800x600
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x06, b'J', b'F', b'I', b'F']);
+        jpeg.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20, 0x03]);
+        assert_eq!(
+            parse_image_header(&jpeg),
+            Some(ImageInfo { format: ImageFormat::Jpeg, width: 800, height: 600 })
+        );
+        // Truncated before the frame header
+        assert_eq!(parse_image_header(&jpeg[..10]), None);
+    }
+
+    #[test]
+    fn test_webp() {
+        let mut lossy = b"RIFF\x00\x00\x00\x00WEBPVP8 \x00\x00\x00\x00".to_vec();
+        lossy.extend_from_slice(&[0x00; 6]);
+        lossy.extend_from_slice(&640u16.to_le_bytes());
+        lossy.extend_from_slice(&480u16.to_le_bytes());
+        assert_eq!(
+            parse_image_header(&lossy).map(|i| (i.format, i.width, i.height)),
+            Some((ImageFormat::Webp, 640, 480))
+        );
+
+        let mut extended = b"RIFF\x00\x00\x00\x00WEBPVP8X\x0a\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        extended.extend_from_slice(&[0x7F, 0x07, 0x00, 0x3B, 0x04, 0x00]);
+        assert_eq!(
+            parse_image_header(&extended).map(|i| (i.width, i.height)),
+            Some((1920, 1084))
+        );
+
+        let lossless = b"RIFF\x00\x00\x00\x00WEBPVP8L\x00\x00\x00\x00\x2f\x0f\xc0\x03\x00";
+        assert_eq!(
+            parse_image_header(lossless).map(|i| (i.width, i.height)),
+            Some((16, 16))
+        );
+    }
+
+    #[test]
+    fn test_avif() {
+        let mut avif = b"\x00\x00\x00\x14ftypavif\x00\x00\x00\x00mif1".to_vec();
+        avif.extend_from_slice(b"\x00\x00\x00\x14ispe\x00\x00\x00\x00");
+        avif.extend_from_slice(&2048u32.to_be_bytes());
+        avif.extend_from_slice(&1536u32.to_be_bytes());
+        assert_eq!(
+            parse_image_header(&avif),
+            Some(ImageInfo { format: ImageFormat::Avif, width: 2048, height: 1536 })
+        );
+        assert_eq!(parse_image_header(b"<html>not an image</html>"), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_budget() {
+        let options = ProbeOptions {
+            max_bytes_per_image: 1024,
+            max_total_bytes: 0,
+            concurrency: 2,
+        };
+        let urls = vec!["https://some.where/a.png".to_string()];
+        assert!(probe_images(&urls, &options, None).await.is_empty());
+        assert!(cached("https://some.where/a.png").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_probe_cache() {
+        // Nothing listens there: the failure is not remembered
+        let url = "http://127.0.0.1:9/unreachable.png";
+        assert_eq!(probe_image(url, 1024, None).await, None);
+        assert!(cached(url).is_none());
+
+        for i in 0..PROBE_CACHE_LIMIT + 10 {
+            store(&format!("https://some.where/{}.png", i), None);
+        }
+        assert!(PROBE_CACHE.lock().unwrap().len() <= PROBE_CACHE_LIMIT);
+        assert_eq!(cached(&format!("https://some.where/{}.png", PROBE_CACHE_LIMIT + 9)), Some(None));
+    }
+}
@@ -0,0 +1,246 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use std::sync::Mutex;
+
+/// Per-domain extraction profile.
+///
+/// A profile is matched against the candidate links of a page with the same
+/// URL patterns as `Transformation`. Every selector is optional: when it is
+/// missing or finds nothing, the generic extraction result is kept for that field.
+#[derive(Clone, Debug, Default)]
+pub struct SiteProfile {
+    pub patterns: Vec<Regex>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub published: Option<String>,
+    pub content: Option<String>,
+    pub image: Option<String>,
+    /// Elements matching these selectors are removed before content scoring
+    pub strip: Vec<String>,
+}
+
+/// Field values picked from a document with the selectors of a `SiteProfile`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProfileFields {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub published: Option<String>,
+    pub content: Option<String>,
+    pub image: Option<String>,
+}
+
+lazy_static! {
+    static ref PROFILES: Mutex<Vec<SiteProfile>> = Mutex::new(Vec::new());
+}
+
+fn add(profile: SiteProfile) -> usize {
+    if profile.patterns.is_empty() {
+        return 0;
+    }
+    PROFILES.lock().unwrap().push(profile);
+    1
+}
+
+pub fn add_profiles(profiles: Vec<SiteProfile>) -> usize {
+    profiles
+        .into_iter()
+        .map(add)
+        .filter(|&result| result == 1)
+        .count()
+}
+
+pub fn remove_profiles(patterns: Option<Vec<Regex>>) -> usize {
+    let mut profiles = PROFILES.lock().unwrap();
+    let Some(patterns) = patterns else {
+        let removed = profiles.len();
+        profiles.clear();
+        return removed;
+    };
+
+    let before = profiles.len();
+    profiles.retain(|profile| {
+        !profile.patterns.iter().any(|ipattern| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.as_str() == ipattern.as_str())
+        })
+    });
+    before - profiles.len()
+}
+
+pub fn get_profiles() -> Vec<SiteProfile> {
+    PROFILES.lock().unwrap().clone()
+}
+
+/// Find the first registered profile whose patterns match any of the links
+pub fn find_profile(links: &[String]) -> Option<SiteProfile> {
+    let profiles = PROFILES.lock().unwrap();
+    profiles
+        .iter()
+        .find(|profile| {
+            links
+                .iter()
+                .any(|url| profile.patterns.iter().any(|pattern| pattern.is_match(url)))
+        })
+        .cloned()
+}
+
+fn normalize_text(element: &ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn select_first<'a>(document: &'a Html, selector: &Option<String>) -> Option<ElementRef<'a>> {
+    let selector = Selector::parse(selector.as_deref()?).ok()?;
+    document.select(&selector).next()
+}
+
+fn select_text(document: &Html, selector: &Option<String>) -> Option<String> {
+    let element = select_first(document, selector)?;
+    let text = normalize_text(&element);
+    (!text.is_empty()).then_some(text)
+}
+
+fn select_attr(document: &Html, selector: &Option<String>, attrs: &[&str]) -> Option<String> {
+    let element = select_first(document, selector)?;
+    attrs
+        .iter()
+        .filter_map(|attr| element.attr(attr))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+fn select_html(document: &Html, selector: &Option<String>) -> Option<String> {
+    let selector = Selector::parse(selector.as_deref()?).ok()?;
+    let html: String = document
+        .select(&selector)
+        .filter(|element| !normalize_text(element).is_empty())
+        .map(|element| element.html())
+        .collect();
+    (!html.is_empty()).then_some(html)
+}
+
+impl SiteProfile {
+    /// Pick every configured field out of the document
+    pub fn extract(&self, html: &str) -> ProfileFields {
+        let document = Html::parse_document(html);
+        let published = select_attr(&document, &self.published, &["datetime", "content"])
+            .or_else(|| select_text(&document, &self.published));
+        let image = select_attr(
+            &document,
+            &self.image,
+            &["data-src", "src", "content", "href"],
+        );
+
+        ProfileFields {
+            title: select_text(&document, &self.title),
+            author: select_text(&document, &self.author),
+            published,
+            content: select_html(&document, &self.content),
+            image,
+        }
+    }
+
+    /// Remove the elements matched by the `strip` selectors
+    pub fn strip(&self, html: &str) -> String {
+        strip_selectors(html, &self.strip)
+    }
+}
+
+/// Remove every element matching one of the selectors from the document
+pub fn strip_selectors(html: &str, selectors: &[String]) -> String {
+    let selectors: Vec<Selector> = selectors
+        .iter()
+        .filter_map(|s| Selector::parse(s).ok())
+        .collect();
+    if selectors.is_empty() {
+        return html.to_string();
+    }
+
+    let mut document = Html::parse_document(html);
+    let ids: Vec<_> = selectors
+        .iter()
+        .flat_map(|selector| {
+            document
+                .select(selector)
+                .map(|e| e.id())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if ids.is_empty() {
+        return html.to_string();
+    }
+    for id in ids {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+    document.html()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn profile(pattern: &str) -> SiteProfile {
+        SiteProfile {
+            patterns: vec![Regex::new(pattern).unwrap()],
+            title: Some("section > h1".to_string()),
+            author: Some(".byline".to_string()),
+            published: Some("time.published".to_string()),
+            content: Some("article .contentdetail".to_string()),
+            image: Some("article img".to_string()),
+            strip: vec!["address".to_string(), ".sidebar-widget".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_add_and_find_profiles() {
+        let added = add_profiles(vec![
+            profile(r"http(s?)://profile-find\.tld/.*"),
+            SiteProfile::default(),
+        ]);
+        assert_eq!(added, 1);
+
+        assert!(find_profile(&["https://other.tld/a".to_string()]).is_none());
+        assert!(find_profile(&["https://profile-find.tld/a".to_string()]).is_some());
+
+        let removed = remove_profiles(Some(vec![
+            Regex::new(r"http(s?)://profile-find\.tld/.*").unwrap(),
+        ]));
+        assert_eq!(removed, 1);
+        assert!(find_profile(&["https://profile-find.tld/a".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_extract_fields() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let fields = profile("unused").extract(&html);
+
+        assert_eq!(fields.title.as_deref(), Some("Article title here"));
+        assert_eq!(fields.author, None);
+        assert_eq!(fields.published, None);
+        assert_eq!(fields.image.as_deref(), Some("./orange.png"));
+        let content = fields.content.unwrap();
+        assert!(content.contains("rational peach"));
+        assert!(content.contains("watermelon"));
+        assert!(!content.contains("The first fair dog"));
+    }
+
+    #[test]
+    fn test_strip_selectors() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let result = profile("unused").strip(&html);
+
+        assert!(!result.contains("4746 Kelly Drive"));
+        assert!(!result.contains("Some widget here"));
+        assert!(result.contains("The first fair dog"));
+    }
+}
@@ -0,0 +1,200 @@
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Snapshot of https://publicsuffix.org/list/public_suffix_list.dat, ICANN and private sections
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+struct SuffixList {
+    rules: HashSet<String>,
+    /// `*.ck` is stored as `ck`
+    wildcards: HashSet<String>,
+    /// `!www.ck` is stored as `www.ck`
+    exceptions: HashSet<String>,
+}
+
+// Rules are written in Unicode, hosts come punycoded from `url`
+fn to_ascii(domain: &str) -> Option<String> {
+    match url::Host::parse(domain).ok()? {
+        url::Host::Domain(domain) => Some(domain),
+        _ => None,
+    }
+}
+
+impl SuffixList {
+    fn parse(list: &str) -> Self {
+        let mut suffixes = Self {
+            rules: HashSet::new(),
+            wildcards: HashSet::new(),
+            exceptions: HashSet::new(),
+        };
+        for line in list.lines() {
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+            if let Some(exception) = rule.strip_prefix('!') {
+                suffixes.exceptions.extend(to_ascii(exception));
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                suffixes.wildcards.extend(to_ascii(parent));
+            } else {
+                suffixes.rules.extend(to_ascii(rule));
+            }
+        }
+        suffixes
+    }
+
+    // Number of labels of the public suffix of `labels`
+    fn suffix_len(&self, labels: &[&str]) -> usize {
+        for i in 0..labels.len() {
+            let candidate = labels[i..].join(".");
+            if self.exceptions.contains(&candidate) {
+                return labels.len() - i - 1;
+            }
+            if self.rules.contains(&candidate) {
+                return labels.len() - i;
+            }
+            if i + 1 < labels.len() && self.wildcards.contains(&labels[i + 1..].join(".")) {
+                return labels.len() - i;
+            }
+        }
+        // The implicit `*` rule
+        1
+    }
+}
+
+lazy_static! {
+    static ref SUFFIXES: SuffixList = SuffixList::parse(PUBLIC_SUFFIX_LIST);
+}
+
+/// A host split on its public suffix
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DomainParts {
+    /// `news` for `news.bbc.co.uk`, empty when there is none
+    pub subdomain: String,
+    /// `bbc.co.uk`, the public suffix plus one label (eTLD+1)
+    pub registrable: String,
+    /// `co.uk`
+    pub suffix: String,
+}
+
+/// Split a host (or a URL) on its public suffix.
+///
+/// `None` for IP addresses, and for hosts that are a public suffix themselves.
+pub fn domain_parts(host_or_url: &str) -> Option<DomainParts> {
+    let host = match url::Url::parse(host_or_url) {
+        Ok(url) if url.has_host() => url.host_str()?.to_string(),
+        _ => to_ascii(host_or_url)?,
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() || host.starts_with('[') || host.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    let suffix_len = SUFFIXES.suffix_len(&labels);
+    if suffix_len >= labels.len() {
+        return None;
+    }
+    let registrable_at = labels.len() - suffix_len - 1;
+    Some(DomainParts {
+        subdomain: labels[..registrable_at].join("."),
+        registrable: labels[registrable_at..].join("."),
+        suffix: labels[labels.len() - suffix_len..].join("."),
+    })
+}
+
+/// eTLD+1 of a host or URL: `bbc.co.uk` for `https://www.bbc.co.uk/news`
+pub fn registrable_domain(host_or_url: &str) -> Option<String> {
+    domain_parts(host_or_url).map(|parts| parts.registrable)
+}
+
+/// Subdomain of a host or URL, `None` when there is none
+pub fn subdomain(host_or_url: &str) -> Option<String> {
+    domain_parts(host_or_url)
+        .map(|parts| parts.subdomain)
+        .filter(|subdomain| !subdomain.is_empty())
+}
+
+/// Public suffix of a host or URL: `co.uk` for `www.bbc.co.uk`
+pub fn public_suffix(host_or_url: &str) -> Option<String> {
+    domain_parts(host_or_url).map(|parts| parts.suffix)
+}
+
+/// Publisher name derived from a URL when the page names none.
+///
+/// The registrable label is used without its suffix: short ones read as
+/// acronyms (`bbc.co.uk` gives `BBC`), hyphens separate words (`the-verge.com`
+/// gives `The Verge`) and other names are capitalized (`reuters.com` gives `Reuters`).
+pub fn source_name(url: &str) -> String {
+    let Some(parts) = domain_parts(url) else {
+        return url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+    };
+    let label = parts
+        .registrable
+        .strip_suffix(&format!(".{}", parts.suffix))
+        .unwrap_or(&parts.registrable);
+    if label.starts_with("xn--") {
+        return parts.registrable;
+    }
+    label
+        .split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            if word.len() <= 3 && label.len() <= 4 {
+                word.to_uppercase()
+            } else {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_parts() {
+        assert_eq!(
+            domain_parts("https://www.bbc.co.uk/news"),
+            Some(DomainParts {
+                subdomain: "www".to_string(),
+                registrable: "bbc.co.uk".to_string(),
+                suffix: "co.uk".to_string(),
+            })
+        );
+        assert_eq!(registrable_domain("edition.cnn.com").as_deref(), Some("cnn.com"));
+        assert_eq!(subdomain("edition.cnn.com").as_deref(), Some("edition"));
+        assert_eq!(subdomain("cnn.com"), None);
+        // Private section and wildcard/exception rules
+        assert_eq!(registrable_domain("someone.github.io").as_deref(), Some("someone.github.io"));
+        assert_eq!(public_suffix("shop.example.ck").as_deref(), Some("example.ck"));
+        assert_eq!(registrable_domain("www.ck").as_deref(), Some("www.ck"));
+        // IDN rules match punycoded hosts
+        assert_eq!(registrable_domain("https://news.example.公司.cn/").as_deref(), Some("example.xn--55qx5d.cn"));
+        // Unknown TLDs fall back to the `*` rule
+        assert_eq!(registrable_domain("somewhere.any").as_deref(), Some("somewhere.any"));
+
+        assert_eq!(registrable_domain("co.uk"), None);
+        assert_eq!(registrable_domain("http://127.0.0.1/"), None);
+    }
+
+    #[test]
+    fn test_source_name() {
+        assert_eq!(source_name("https://www.bbc.co.uk/news/story"), "BBC");
+        assert_eq!(source_name("https://edition.cnn.com/2024/05/story"), "CNN");
+        assert_eq!(source_name("https://www.reuters.com/markets"), "Reuters");
+        assert_eq!(source_name("https://the-verge.com/"), "The Verge");
+        assert_eq!(source_name("http://127.0.0.1/page"), "127.0.0.1");
+    }
+}
@@ -0,0 +1,224 @@
+use crate::paywall::AccessStatus;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::Serialize;
+
+/// Phrases that usually come from page chrome rather than from the article
+const BOILERPLATE_PHRASES: &[&str] = &[
+    "cookie",
+    "subscribe",
+    "sign in",
+    "sign up",
+    "log in",
+    "newsletter",
+    "all rights reserved",
+    "privacy policy",
+    "terms of use",
+    "accept all",
+    "advertisement",
+    "enable javascript",
+];
+
+/// Signals describing how trustworthy an extraction is.
+///
+/// `confidence` combines the other fields into a score between 0 and 1.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QualitySignals {
+    pub confidence: f64,
+    /// Characters of extracted text
+    pub text_length: usize,
+    /// Share of the extracted text found inside links
+    pub link_density: f64,
+    pub paragraph_count: usize,
+    /// Extracted text length divided by the text length of the whole page
+    pub text_ratio: f64,
+    /// Characters of visible text on the whole page, summed over merged pages
+    pub page_text_length: usize,
+    /// Whether most words of the title appear in the extracted text
+    pub title_in_content: bool,
+    /// Boilerplate phrases found in the extracted text
+    pub boilerplate_hits: Vec<String>,
+    /// Whether `paywall::detect_access` found the content behind a paywall
+    pub paywall_marker: bool,
+}
+
+fn text_length(text: &str) -> usize {
+    text.split_whitespace().map(|w| w.chars().count()).sum()
+}
+
+// Visible text of the page body, without scripts and styles
+fn page_text(document: &Html) -> String {
+    let selector = Selector::parse("body").unwrap();
+    let Some(body) = document.select(&selector).next() else {
+        return String::new();
+    };
+    body.descendants()
+        .filter_map(|node| match node.value() {
+            Node::Text(t) => {
+                let hidden = node.ancestors().filter_map(ElementRef::wrap).any(|e| {
+                    matches!(e.value().name(), "script" | "style" | "noscript" | "template")
+                });
+                (!hidden).then(|| t.to_string())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn title_in_content(title: &str, text: &str) -> bool {
+    let text = text.to_lowercase();
+    let words: Vec<String> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 2)
+        .map(|w| w.to_lowercase())
+        .collect();
+    if words.is_empty() {
+        return false;
+    }
+    let found = words.iter().filter(|w| text.contains(w.as_str())).count();
+    found * 2 >= words.len()
+}
+
+impl QualitySignals {
+    fn score(&self) -> f64 {
+        let length = (self.text_length as f64 / 2000.0).min(1.0);
+        let links = 1.0 - (self.link_density * 2.0).min(1.0);
+        let paragraphs = (self.paragraph_count as f64 / 5.0).min(1.0);
+        let ratio = (self.text_ratio / 0.3).min(1.0);
+        let title = if self.title_in_content { 1.0 } else { 0.0 };
+
+        let mut score =
+            0.35 * length + 0.15 * links + 0.15 * paragraphs + 0.2 * ratio + 0.15 * title;
+        score -= (0.05 * self.boilerplate_hits.len() as f64).min(0.2);
+        if self.paywall_marker {
+            score *= 0.5;
+        }
+        score.clamp(0.0, 1.0)
+    }
+}
+
+/// Compute the quality signals of an extraction.
+///
+/// `content` is the extracted HTML, `text` its plain text, `page_html` the
+/// HTML of the whole page the content was taken from and `access` what
+/// `paywall::detect_access` made of it.
+pub fn assess(content: &str, text: &str, page_html: &str, title: &str, access: AccessStatus) -> QualitySignals {
+    let page_len = text_length(&page_text(&Html::parse_document(page_html)));
+    signals(content, text, page_len, access == AccessStatus::Paywalled, title)
+}
+
+/// Quality signals of content merged from several pages, each assessed on its own
+pub fn assess_merged(content: &str, text: &str, title: &str, pages: &[&QualitySignals]) -> QualitySignals {
+    let page_len = pages.iter().map(|page| page.page_text_length).sum();
+    let paywall_marker = pages.iter().any(|page| page.paywall_marker);
+    signals(content, text, page_len, paywall_marker, title)
+}
+
+fn signals(content: &str, text: &str, page_len: usize, paywall_marker: bool, title: &str) -> QualitySignals {
+    let fragment = Html::parse_fragment(content);
+    let text_len = text_length(text);
+
+    let link_selector = Selector::parse("a").unwrap();
+    let link_length: usize = fragment
+        .select(&link_selector)
+        .map(|a| text_length(&a.text().collect::<String>()))
+        .sum();
+    let paragraph_selector = Selector::parse("p").unwrap();
+    let paragraph_count = fragment
+        .select(&paragraph_selector)
+        .filter(|p| p.text().any(|t| !t.trim().is_empty()))
+        .count();
+
+    let lowered = text.to_lowercase();
+    let boilerplate_hits = BOILERPLATE_PHRASES
+        .iter()
+        .filter(|phrase| lowered.contains(*phrase))
+        .map(|phrase| phrase.to_string())
+        .collect();
+
+    let mut signals = QualitySignals {
+        confidence: 0.0,
+        text_length: text_len,
+        link_density: if text_len > 0 {
+            link_length as f64 / text_len as f64
+        } else {
+            0.0
+        },
+        paragraph_count,
+        text_ratio: if page_len > 0 {
+            (text_len as f64 / page_len as f64).min(1.0)
+        } else {
+            0.0
+        },
+        page_text_length: page_len,
+        title_in_content: title_in_content(title, text),
+        boilerplate_hits,
+        paywall_marker,
+    };
+    signals.confidence = signals.score();
+    signals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"
+        <html><body>
+            <nav><a href="/">Home</a></nav>
+            <article>
+                <h1>Rates rise again</h1>
+                <p>The central bank raised rates again on Wednesday, the fifth increase this year.</p>
+                <p>Economists expect borrowing costs to keep rising through the end of the year.</p>
+                <p>Mortgage rates already sit at their highest level in more than a decade.</p>
+            </article>
+            <script>var tracking = "ignored";</script>
+        </body></html>
+    "#;
+
+    #[test]
+    fn test_assess_good_extraction() {
+        let content = "<p>The central bank raised rates again on Wednesday, the fifth increase this year.</p><p>Economists expect borrowing costs to keep rising through the end of the year.</p><p>Mortgage rates already sit at their highest level in more than a decade.</p>";
+        let text = "The central bank raised rates again on Wednesday, the fifth increase this year. Economists expect borrowing costs to keep rising through the end of the year. Mortgage rates already sit at their highest level in more than a decade.";
+        let signals = assess(content, text, PAGE, "Rates rise again", AccessStatus::Open);
+
+        assert_eq!(signals.paragraph_count, 3);
+        assert_eq!(signals.link_density, 0.0);
+        assert!(signals.text_ratio > 0.8);
+        assert!(signals.title_in_content);
+        assert!(signals.boilerplate_hits.is_empty());
+        assert!(!signals.paywall_marker);
+        assert!(signals.confidence > 0.5);
+    }
+
+    #[test]
+    fn test_assess_paywall_word_on_open_page() {
+        // Script bundles and class names mention paywalls on open articles too
+        let page = PAGE.replace("var tracking", r#"var paywall = {"enabled": false}; var tracking"#)
+            .replace("<article>", r#"<article class="no-paywall">"#);
+        let content = "<p>The central bank raised rates again on Wednesday, the fifth increase this year.</p>";
+        let text = "The central bank raised rates again on Wednesday, the fifth increase this year.";
+        let access = crate::paywall::detect_access(&page, &page_text(&Html::parse_document(&page)));
+        assert_eq!(access, AccessStatus::Open);
+        let signals = assess(content, text, &page, "Rates rise again", access);
+        assert!(!signals.paywall_marker);
+    }
+
+    #[test]
+    fn test_assess_boilerplate_extraction() {
+        let content = r#"<div><a href="/login">Sign in</a> to accept all cookie settings and subscribe.</div>"#;
+        let text = "Sign in to accept all cookie settings and subscribe.";
+        let page = format!(r#"{}<div class="paywall"></div>"#, PAGE);
+        let signals = assess(content, text, &page, "Rates rise again", AccessStatus::Paywalled);
+
+        assert_eq!(signals.paragraph_count, 0);
+        assert!(signals.link_density > 0.1);
+        assert!(!signals.title_in_content);
+        assert_eq!(
+            signals.boilerplate_hits,
+            vec!["cookie", "subscribe", "sign in", "accept all"]
+        );
+        assert!(signals.paywall_marker);
+        assert!(signals.confidence < 0.2);
+    }
+}
@@ -0,0 +1,990 @@
+use ego_tree::{NodeId, NodeRef};
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use url::Url;
+
+/// Weights and thresholds of the content scoring algorithm.
+///
+/// The defaults follow Mozilla's Readability. The class/id patterns are
+/// matched case-insensitively against `class` and `id` attributes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReadabilityOptions {
+    /// Minimum length of the extracted text before a retry with relaxed flags
+    pub char_threshold: usize,
+    /// Number of top candidates kept for ancestor analysis and debugging
+    pub n_top_candidates: usize,
+    /// Paragraphs shorter than this are not scored
+    pub min_paragraph_length: usize,
+    /// One point is given for every `chars_per_point` characters of a paragraph
+    pub chars_per_point: usize,
+    /// Cap on the points given for paragraph length
+    pub max_length_points: f64,
+    /// Weight added or removed when class/id match the positive/negative patterns
+    pub class_weight: f64,
+    /// Siblings scoring above `top score * sibling_score_ratio` are merged
+    pub sibling_score_ratio: f64,
+    /// Lower bound for the sibling merge threshold
+    pub min_sibling_score: f64,
+    /// Unscored paragraph siblings longer than `sibling_min_length` are merged
+    /// when their link density is below this value
+    pub sibling_link_density: f64,
+    pub sibling_min_length: usize,
+    /// Containers with at least this many commas are never cleaned conditionally
+    pub clean_comma_threshold: usize,
+    /// Maximum link density of a conditionally cleaned container with a low weight
+    pub max_link_density: f64,
+    /// Maximum link density of a conditionally cleaned container with a high weight
+    pub max_weighted_link_density: f64,
+    pub positive_pattern: String,
+    pub negative_pattern: String,
+    pub unlikely_pattern: String,
+    pub maybe_pattern: String,
+}
+
+impl Default for ReadabilityOptions {
+    fn default() -> Self {
+        Self {
+            char_threshold: 500,
+            n_top_candidates: 5,
+            min_paragraph_length: 25,
+            chars_per_point: 100,
+            max_length_points: 3.0,
+            class_weight: 25.0,
+            sibling_score_ratio: 0.2,
+            min_sibling_score: 10.0,
+            sibling_link_density: 0.25,
+            sibling_min_length: 80,
+            clean_comma_threshold: 10,
+            max_link_density: 0.2,
+            max_weighted_link_density: 0.5,
+            positive_pattern: "article|body|content|entry|hentry|h-entry|main|page|pagination|post|text|blog|story".to_string(),
+            negative_pattern: "-1|hidden|^hid$| hid$| hid |^hid |banner|combx|comment|com-|contact|foot|footer|footnote|gdpr|masthead|media|meta|outbrain|promo|related|scroll|share|shoutbox|sidebar|skyscraper|sponsor|shopping|tags|tool|widget".to_string(),
+            unlikely_pattern: "-ad-|ai2html|banner|breadcrumbs|combx|comment|community|cover-wrap|disqus|extra|footer|gdpr|header|legends|menu|related|remark|replies|rss|shoutbox|sidebar|skyscraper|social|sponsor|supplemental|ad-break|agegate|pagination|pager|popup|yom-remote".to_string(),
+            maybe_pattern: "and|article|body|column|content|main|shadow".to_string(),
+        }
+    }
+}
+
+/// A scored element, reported for debugging
+#[derive(Clone, Debug, Serialize)]
+pub struct Candidate {
+    /// CSS-like path of the element from `body`
+    pub path: String,
+    pub score: f64,
+    pub link_density: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReadabilityArticle {
+    pub title: String,
+    pub content: String,
+    pub text_length: usize,
+    /// Top candidates of the winning attempt, best first
+    pub candidates: Vec<Candidate>,
+}
+
+const UNLIKELY_ROLES: &[&str] = &[
+    "menu",
+    "menubar",
+    "complementary",
+    "navigation",
+    "alert",
+    "alertdialog",
+    "dialog",
+];
+
+const ALWAYS_REMOVED: &[&str] = &[
+    "script", "style", "noscript", "template", "link", "meta", "form", "input", "button", "select",
+    "textarea", "footer", "aside", "nav", "object", "embed",
+];
+
+// Tags whose presence keeps a div from being treated as a paragraph
+const BLOCK_TAGS: &[&str] = &[
+    "blockquote",
+    "dl",
+    "div",
+    "img",
+    "ol",
+    "p",
+    "pre",
+    "table",
+    "ul",
+    "section",
+    "article",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+const CONDITIONAL_TAGS: &[&str] = &["form", "fieldset", "table", "ul", "div", "section"];
+
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// `id` is kept, like Mozilla's Readability does, for headings and `#anchor` targets
+const DROPPED_ATTRIBUTES: &[&str] = &[
+    "style", "class", "align", "bgcolor", "border", "valign",
+];
+
+const VIDEO_HOSTS: &str = r"(?i)//(www\.)?((dailymotion|youtube|youtube-nocookie|player\.vimeo|v\.qq)\.com|(archive|upload\.wikimedia)\.org|player\.twitch\.tv)";
+
+lazy_static! {
+    static ref DATA_TABLE_MARKERS: Selector =
+        Selector::parse("caption, thead, tfoot, th, colgroup").unwrap();
+    static ref TABLE: Selector = Selector::parse("table").unwrap();
+    static ref ROW: Selector = Selector::parse("tr").unwrap();
+    static ref CELL: Selector = Selector::parse("td").unwrap();
+}
+
+#[derive(Clone, Copy)]
+struct Flags {
+    strip_unlikelys: bool,
+    weight_classes: bool,
+    clean_conditionally: bool,
+}
+
+struct Patterns {
+    positive: Regex,
+    negative: Regex,
+    unlikely: Regex,
+    maybe: Regex,
+    video: Regex,
+    sentence_end: Regex,
+}
+
+impl Patterns {
+    fn new(options: &ReadabilityOptions) -> Option<Self> {
+        let build = |p: &str| Regex::new(&format!("(?i){}", p)).ok();
+        Some(Self {
+            positive: build(&options.positive_pattern)?,
+            negative: build(&options.negative_pattern)?,
+            unlikely: build(&options.unlikely_pattern)?,
+            maybe: build(&options.maybe_pattern)?,
+            video: Regex::new(VIDEO_HOSTS).ok()?,
+            sentence_end: Regex::new(r"\.( |$)").ok()?,
+        })
+    }
+}
+
+struct Scorer<'a> {
+    options: &'a ReadabilityOptions,
+    patterns: &'a Patterns,
+    flags: Flags,
+    base: Option<Url>,
+    removed: HashSet<NodeId>,
+    scores: HashMap<NodeId, f64>,
+    // Per-node results reused while cleaning nested containers; they hold once
+    // `mark_removed` is done
+    texts: RefCell<HashMap<NodeId, Rc<str>>>,
+    text_lengths: RefCell<HashMap<NodeId, usize>>,
+    link_lengths: RefCell<HashMap<NodeId, usize>>,
+    data_tables: RefCell<HashMap<NodeId, bool>>,
+    table_wrappers: RefCell<HashMap<NodeId, bool>>,
+}
+
+fn tag_name<'a>(element: &ElementRef<'a>) -> &'a str {
+    element.value().name()
+}
+
+fn child_elements<'a>(node: NodeRef<'a, Node>) -> impl Iterator<Item = ElementRef<'a>> {
+    node.children().filter_map(ElementRef::wrap)
+}
+
+fn count_commas(text: &str) -> usize {
+    text.chars().filter(|c| *c == ',' || *c == '，').count()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\u{a0}', "&nbsp;")
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('\u{a0}', "&nbsp;")
+}
+
+fn has_ancestor_tag(element: &ElementRef, tags: &[&str]) -> bool {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .any(|a| tags.contains(&tag_name(&a)))
+}
+
+fn describe(element: &ElementRef) -> String {
+    let mut path: Vec<String> = element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .take_while(|a| tag_name(a) != "html")
+        .map(|a| describe_one(&a))
+        .collect();
+    path.reverse();
+    path.push(describe_one(element));
+    path.join(" > ")
+}
+
+fn describe_one(element: &ElementRef) -> String {
+    let mut desc = tag_name(element).to_string();
+    if let Some(id) = element.value().id() {
+        desc.push('#');
+        desc.push_str(id);
+    }
+    for class in element.value().classes() {
+        desc.push('.');
+        desc.push_str(class);
+    }
+    desc
+}
+
+/// Whether a table holds data rather than page layout
+pub fn is_data_table(table: &ElementRef) -> bool {
+    if table.attr("role") == Some("presentation") || table.attr("datatable") == Some("0") {
+        return false;
+    }
+    if table.attr("summary").is_some() {
+        return true;
+    }
+    if table.select(&DATA_TABLE_MARKERS).next().is_some() {
+        return true;
+    }
+    if table.select(&TABLE).next().is_some() {
+        return false;
+    }
+    let rows = table.select(&ROW).count();
+    let columns = table
+        .select(&ROW)
+        .map(|tr| {
+            tr.select(&CELL)
+                .map(|td| {
+                    td.attr("colspan")
+                        .and_then(|c| c.parse().ok())
+                        .unwrap_or(1usize)
+                })
+                .sum::<usize>()
+        })
+        .max()
+        .unwrap_or(0);
+    rows >= 10 || columns > 4 || rows * columns > 10
+}
+
+impl<'a> Scorer<'a> {
+    fn new(
+        options: &'a ReadabilityOptions,
+        patterns: &'a Patterns,
+        flags: Flags,
+        url: &str,
+    ) -> Self {
+        Self {
+            options,
+            patterns,
+            flags,
+            base: Url::parse(url).ok(),
+            removed: HashSet::new(),
+            scores: HashMap::new(),
+            texts: RefCell::new(HashMap::new()),
+            text_lengths: RefCell::new(HashMap::new()),
+            link_lengths: RefCell::new(HashMap::new()),
+            data_tables: RefCell::new(HashMap::new()),
+            table_wrappers: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Text of the nodes that are not removed, built from the cached text of the children
+    fn text(&self, node: NodeRef<Node>) -> Rc<str> {
+        if let Some(text) = self.texts.borrow().get(&node.id()) {
+            return text.clone();
+        }
+        let mut text = String::new();
+        for child in node.children() {
+            if self.removed.contains(&child.id()) {
+                continue;
+            }
+            match child.value() {
+                Node::Text(t) => text.push_str(t),
+                Node::Element(_) => text.push_str(&self.text(child)),
+                _ => {}
+            }
+        }
+        let text: Rc<str> = text.into();
+        self.texts.borrow_mut().insert(node.id(), text.clone());
+        text
+    }
+
+    fn text_length(&self, node: NodeRef<Node>) -> usize {
+        if let Some(length) = self.text_lengths.borrow().get(&node.id()) {
+            return *length;
+        }
+        let length = self
+            .text(node)
+            .split_whitespace()
+            .map(|w| w.chars().count() + 1)
+            .sum::<usize>()
+            .saturating_sub(1);
+        self.text_lengths.borrow_mut().insert(node.id(), length);
+        length
+    }
+
+    fn is_data_table(&self, table: &ElementRef) -> bool {
+        if let Some(data) = self.data_tables.borrow().get(&table.id()) {
+            return *data;
+        }
+        let data = is_data_table(table);
+        self.data_tables.borrow_mut().insert(table.id(), data);
+        data
+    }
+
+    // Whether a data table sits below the element, from the cached answers of its children
+    fn contains_data_table(&self, element: &ElementRef) -> bool {
+        if let Some(found) = self.table_wrappers.borrow().get(&element.id()) {
+            return *found;
+        }
+        let found = child_elements(**element).any(|child| {
+            (tag_name(&child) == "table" && self.is_data_table(&child))
+                || self.contains_data_table(&child)
+        });
+        self.table_wrappers.borrow_mut().insert(element.id(), found);
+        found
+    }
+
+    fn link_density(&self, element: &ElementRef) -> f64 {
+        let length = self.text_length(**element);
+        if length == 0 {
+            return 0.0;
+        }
+        self.link_length(**element) as f64 / length as f64
+    }
+
+    // Text length of the links below a node, summed from the cached lengths of its children
+    fn link_length(&self, node: NodeRef<Node>) -> usize {
+        if let Some(length) = self.link_lengths.borrow().get(&node.id()) {
+            return *length;
+        }
+        let length = child_elements(node)
+            .filter(|child| !self.removed.contains(&child.id()))
+            .map(|child| {
+                let own = if tag_name(&child) == "a" { self.text_length(*child) } else { 0 };
+                own + self.link_length(*child)
+            })
+            .sum();
+        self.link_lengths.borrow_mut().insert(node.id(), length);
+        length
+    }
+
+    fn is_removed(&self, element: &ElementRef) -> bool {
+        self.removed.contains(&element.id())
+            || element.ancestors().any(|a| self.removed.contains(&a.id()))
+    }
+
+    fn class_weight(&self, element: &ElementRef) -> f64 {
+        if !self.flags.weight_classes {
+            return 0.0;
+        }
+        let mut weight = 0.0;
+        let attrs = [element.attr("class"), element.attr("id")];
+        for value in attrs.into_iter().flatten().filter(|v| !v.is_empty()) {
+            if self.patterns.negative.is_match(value) {
+                weight -= self.options.class_weight;
+            }
+            if self.patterns.positive.is_match(value) {
+                weight += self.options.class_weight;
+            }
+        }
+        weight
+    }
+
+    fn initial_score(&self, element: &ElementRef) -> f64 {
+        let base = match tag_name(element) {
+            "div" => 5.0,
+            "pre" | "td" | "blockquote" => 3.0,
+            "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        };
+        base + self.class_weight(element)
+    }
+
+    fn is_hidden(element: &ElementRef) -> bool {
+        let style = element
+            .attr("style")
+            .unwrap_or("")
+            .replace(' ', "")
+            .to_lowercase();
+        element.attr("hidden").is_some()
+            || element.attr("aria-hidden") == Some("true")
+            || style.contains("display:none")
+            || style.contains("visibility:hidden")
+    }
+
+    // First pass: drop invisible nodes and, when enabled, unlikely candidates
+    fn mark_removed(&mut self, node: NodeRef<Node>) {
+        for child in child_elements(node) {
+            let tag = tag_name(&child);
+            let unlikely = self.flags.strip_unlikelys && self.is_unlikely(&child);
+            if matches!(tag, "script" | "style" | "noscript" | "template")
+                || Self::is_hidden(&child)
+                || unlikely
+            {
+                self.removed.insert(child.id());
+                continue;
+            }
+            self.mark_removed(*child);
+        }
+    }
+
+    fn is_unlikely(&self, element: &ElementRef) -> bool {
+        let tag = tag_name(element);
+        if matches!(tag, "html" | "body" | "a") || has_ancestor_tag(element, &["table", "code"]) {
+            return false;
+        }
+        if element
+            .attr("role")
+            .is_some_and(|r| UNLIKELY_ROLES.contains(&r))
+        {
+            return true;
+        }
+        let match_string = format!(
+            "{} {}",
+            element.attr("class").unwrap_or(""),
+            element.attr("id").unwrap_or("")
+        );
+        self.patterns.unlikely.is_match(&match_string)
+            && !self.patterns.maybe.is_match(&match_string)
+    }
+
+    fn is_paragraph_like(&self, element: &ElementRef) -> bool {
+        match tag_name(element) {
+            "p" | "pre" | "td" => true,
+            "div" | "section" | "article" => !element
+                .descendants()
+                .skip(1)
+                .filter_map(ElementRef::wrap)
+                .any(|e| BLOCK_TAGS.contains(&tag_name(&e)) && !self.is_removed(&e)),
+            _ => false,
+        }
+    }
+
+    fn add_score(&mut self, element: ElementRef, amount: f64) {
+        let initial = self.initial_score(&element);
+        *self.scores.entry(element.id()).or_insert(initial) += amount;
+    }
+
+    fn paragraph_score(&self, text: &str) -> Option<f64> {
+        let length = text.trim().chars().count();
+        if length < self.options.min_paragraph_length {
+            return None;
+        }
+        let length_points = ((length / self.options.chars_per_point.max(1)) as f64)
+            .min(self.options.max_length_points);
+        Some(1.0 + count_commas(text) as f64 + length_points)
+    }
+
+    // Propagate a paragraph score to its ancestors, decaying with the distance
+    fn propagate(&mut self, start: Option<ElementRef>, score: f64) {
+        let ancestors = std::iter::successors(start, |e| e.parent().and_then(ElementRef::wrap))
+            .take_while(|e| tag_name(e) != "html")
+            .take(5)
+            .collect::<Vec<_>>();
+        for (level, ancestor) in ancestors.into_iter().enumerate() {
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                _ => level as f64 * 3.0,
+            };
+            self.add_score(ancestor, score / divider);
+        }
+    }
+
+    // Second pass: score paragraphs and loose text runs into their ancestors
+    fn score_paragraphs(&mut self, node: NodeRef<Node>) {
+        for child in child_elements(node) {
+            if self.removed.contains(&child.id()) {
+                continue;
+            }
+            if self.is_paragraph_like(&child) {
+                let text = self.text(*child);
+                if let Some(score) = self.paragraph_score(&text) {
+                    let parent = child.parent().and_then(ElementRef::wrap);
+                    self.propagate(parent, score);
+                }
+                continue;
+            }
+            let loose: String = child
+                .children()
+                .filter_map(|c| c.value().as_text().map(|t| t.to_string()))
+                .collect();
+            if let Some(score) = self.paragraph_score(&loose) {
+                self.propagate(Some(child), score);
+            }
+            self.score_paragraphs(*child);
+        }
+    }
+
+    fn final_score(&self, element: &ElementRef) -> f64 {
+        self.scores.get(&element.id()).copied().unwrap_or(0.0) * (1.0 - self.link_density(element))
+    }
+
+    fn top_candidates<'d>(&self, document: &'d Html) -> Vec<(ElementRef<'d>, f64)> {
+        let mut candidates: Vec<(ElementRef<'d>, f64)> = self
+            .scores
+            .keys()
+            .filter_map(|id| document.tree.get(*id).and_then(ElementRef::wrap))
+            .map(|e| (e, self.final_score(&e)))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.truncate(self.options.n_top_candidates.max(1));
+        candidates
+    }
+
+    fn select_top<'d>(
+        &self,
+        body: ElementRef<'d>,
+        candidates: &[(ElementRef<'d>, f64)],
+    ) -> (ElementRef<'d>, f64) {
+        let Some(&(mut top, top_score)) = candidates.first() else {
+            return (body, 0.0);
+        };
+        if tag_name(&top) == "body" {
+            return (top, top_score);
+        }
+
+        // When several strong candidates share an ancestor, that ancestor holds the article
+        let alternatives: Vec<HashSet<NodeId>> = candidates[1..]
+            .iter()
+            .filter(|(_, score)| top_score > 0.0 && score / top_score >= 0.75)
+            .map(|(e, _)| e.ancestors().map(|a| a.id()).collect())
+            .collect();
+        if alternatives.len() >= 3 {
+            let mut parent = top.parent().and_then(ElementRef::wrap);
+            while let Some(p) = parent.filter(|p| tag_name(p) != "body") {
+                if alternatives.iter().filter(|a| a.contains(&p.id())).count() >= 3 {
+                    top = p;
+                    break;
+                }
+                parent = p.parent().and_then(ElementRef::wrap);
+            }
+        }
+
+        // Climb while the parent scores better than the current candidate
+        let threshold = self.final_score(&top) / 3.0;
+        let mut last_score = self.final_score(&top);
+        let mut parent = top.parent().and_then(ElementRef::wrap);
+        while let Some(p) = parent.filter(|p| tag_name(p) != "body") {
+            if self.scores.contains_key(&p.id()) {
+                let parent_score = self.final_score(&p);
+                if parent_score < threshold {
+                    break;
+                }
+                if parent_score > last_score {
+                    top = p;
+                    break;
+                }
+                last_score = parent_score;
+            }
+            parent = p.parent().and_then(ElementRef::wrap);
+        }
+
+        // A candidate that is the only child gets replaced by its parent
+        while let Some(p) = top.parent().and_then(ElementRef::wrap) {
+            if tag_name(&p) == "body"
+                || child_elements(*p).filter(|c| !self.is_removed(c)).count() != 1
+            {
+                break;
+            }
+            top = p;
+        }
+        let score = self.final_score(&top);
+        (top, score)
+    }
+
+    fn merge_siblings<'d>(&self, top: ElementRef<'d>, top_score: f64) -> Vec<ElementRef<'d>> {
+        let parent = match top.parent().and_then(ElementRef::wrap) {
+            Some(parent) if tag_name(&top) != "body" => parent,
+            _ => return vec![top],
+        };
+        let threshold = self
+            .options
+            .min_sibling_score
+            .max(top_score * self.options.sibling_score_ratio);
+        let top_class = top.attr("class").unwrap_or("");
+
+        child_elements(*parent)
+            .filter(|sibling| !self.is_removed(sibling))
+            .filter(|sibling| {
+                if sibling.id() == top.id() {
+                    return true;
+                }
+                let bonus = if !top_class.is_empty() && sibling.attr("class") == Some(top_class) {
+                    top_score * 0.2
+                } else {
+                    0.0
+                };
+                if self.scores.contains_key(&sibling.id())
+                    && self.final_score(sibling) + bonus >= threshold
+                {
+                    return true;
+                }
+                if tag_name(sibling) == "p" {
+                    let density = self.link_density(sibling);
+                    let text = self.text(**sibling);
+                    let length = text.trim().chars().count();
+                    if length > self.options.sibling_min_length
+                        && density < self.options.sibling_link_density
+                    {
+                        return true;
+                    }
+                    return length > 0
+                        && length <= self.options.sibling_min_length
+                        && density == 0.0
+                        && self.patterns.sentence_end.is_match(&text);
+                }
+                false
+            })
+            .collect()
+    }
+
+    fn has_video(&self, element: &ElementRef) -> bool {
+        element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .filter(|e| matches!(tag_name(e), "iframe" | "embed" | "object" | "video"))
+            .any(|e| {
+                tag_name(&e) == "video"
+                    || e.value()
+                        .attrs()
+                        .any(|(_, v)| self.patterns.video.is_match(v))
+            })
+    }
+
+    fn should_clean(&self, element: &ElementRef) -> bool {
+        let tag = tag_name(element);
+        if matches!(tag, "h1" | "h2")
+            && self.flags.weight_classes
+            && self.class_weight(element) < 0.0
+        {
+            return true;
+        }
+        if !self.flags.clean_conditionally || !CONDITIONAL_TAGS.contains(&tag) {
+            return false;
+        }
+        if tag == "table" && self.is_data_table(element) {
+            return false;
+        }
+        // Wrappers around data tables would take the table down with them
+        if tag != "table" && self.contains_data_table(element) {
+            return false;
+        }
+        if has_ancestor_tag(element, &["code", "pre"]) {
+            return false;
+        }
+        let weight = self.class_weight(element);
+        if weight < 0.0 {
+            return true;
+        }
+        let text = self.text(**element);
+        if count_commas(&text) >= self.options.clean_comma_threshold {
+            return false;
+        }
+        if self.has_video(element) {
+            return false;
+        }
+
+        let count = |tags: &[&str]| {
+            element
+                .descendants()
+                .skip(1)
+                .filter_map(ElementRef::wrap)
+                .filter(|e| tags.contains(&tag_name(e)) && !self.is_removed(e))
+                .count() as f64
+        };
+        let paragraphs = count(&["p"]);
+        let images = count(&["img"]);
+        let list_items = count(&["li"]) - 100.0;
+        let inputs = count(&["input"]);
+        let embeds = count(&["object", "embed", "iframe"]);
+        let is_list = matches!(tag, "ul" | "ol");
+        let in_figure = has_ancestor_tag(element, &["figure"]);
+        let link_density = self.link_density(element);
+        let content_length = text.trim().chars().count();
+
+        (images > 1.0 && paragraphs / images < 0.5 && !in_figure)
+            || (!is_list && list_items > paragraphs)
+            || inputs > (paragraphs / 3.0).floor()
+            || (!is_list && content_length < 25 && (images == 0.0 || images > 2.0) && !in_figure)
+            || (!is_list && weight < 25.0 && link_density > self.options.max_link_density)
+            || (weight >= 25.0 && link_density > self.options.max_weighted_link_density)
+            || ((embeds == 1.0 && content_length < 75) || embeds > 1.0)
+    }
+
+    fn resolve(&self, value: &str) -> String {
+        match &self.base {
+            Some(base) => base
+                .join(value)
+                .map(|u| u.to_string())
+                .unwrap_or_else(|_| value.to_string()),
+            None => value.to_string(),
+        }
+    }
+
+    fn serialize(&self, node: NodeRef<Node>, html: &mut String, text: &mut String) {
+        match node.value() {
+            Node::Text(t) => {
+                html.push_str(&escape_text(t));
+                text.push_str(t);
+            }
+            Node::Element(element) => {
+                let Some(element_ref) = ElementRef::wrap(node) else {
+                    return;
+                };
+                let tag = element.name();
+                if self.removed.contains(&node.id())
+                    || (ALWAYS_REMOVED.contains(&tag) && !self.has_video(&element_ref))
+                    || self.should_clean(&element_ref)
+                {
+                    return;
+                }
+                html.push('<');
+                html.push_str(tag);
+                for (name, value) in element.attrs() {
+                    if DROPPED_ATTRIBUTES.contains(&name) || name.starts_with("on") {
+                        continue;
+                    }
+                    let value = if matches!(name, "href" | "src") {
+                        self.resolve(value)
+                    } else {
+                        value.to_string()
+                    };
+                    html.push_str(&format!(" {}=\"{}\"", name, escape_attribute(&value)));
+                }
+                html.push('>');
+                if VOID_TAGS.contains(&tag) {
+                    return;
+                }
+                for child in node.children() {
+                    self.serialize(child, html, text);
+                }
+                html.push_str(&format!("</{}>", tag));
+            }
+            _ => {}
+        }
+    }
+
+    fn run(&mut self, document: &Html) -> Option<(String, String, Vec<Candidate>)> {
+        let body_selector = Selector::parse("body").unwrap();
+        let body = document.select(&body_selector).next()?;
+        self.mark_removed(*body);
+        self.score_paragraphs(*body);
+
+        let candidates = self.top_candidates(document);
+        let (top, top_score) = self.select_top(body, &candidates);
+
+        let mut html = String::new();
+        let mut text = String::new();
+        for element in self.merge_siblings(top, top_score) {
+            if element.id() == top.id() {
+                for child in element.children() {
+                    self.serialize(child, &mut html, &mut text);
+                }
+            } else {
+                self.serialize(*element, &mut html, &mut text);
+            }
+        }
+
+        let candidates = candidates
+            .iter()
+            .map(|(e, score)| Candidate {
+                path: describe(e),
+                score: *score,
+                link_density: self.link_density(e),
+            })
+            .collect();
+        Some((html.trim().to_string(), text, candidates))
+    }
+}
+
+/// Title of the document, taken from the `<title>` element
+pub fn get_title(document: &Html) -> Option<String> {
+    let selector = Selector::parse("head > title, title").unwrap();
+    let title = document
+        .select(&selector)
+        .next()?
+        .text()
+        .collect::<String>();
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// Extract the main content of a parsed document.
+///
+/// The scoring runs with every flag enabled first; while the result is shorter
+/// than `char_threshold`, it retries without stripping unlikely candidates, then
+/// without class weights, then without conditional cleaning, and keeps the longest
+/// attempt.
+pub fn extract(
+    document: &Html,
+    url: &str,
+    options: &ReadabilityOptions,
+) -> Option<ReadabilityArticle> {
+    let patterns = Patterns::new(options)?;
+    let mut flags = Flags {
+        strip_unlikelys: true,
+        weight_classes: true,
+        clean_conditionally: true,
+    };
+    let mut attempts: Vec<ReadabilityArticle> = Vec::new();
+
+    loop {
+        let mut scorer = Scorer::new(options, &patterns, flags, url);
+        let (content, text, candidates) = scorer.run(document)?;
+        let text_length = text
+            .split_whitespace()
+            .map(|w| w.chars().count() + 1)
+            .sum::<usize>()
+            .saturating_sub(1);
+        attempts.push(ReadabilityArticle {
+            title: get_title(document).unwrap_or_default(),
+            content,
+            text_length,
+            candidates,
+        });
+        if text_length >= options.char_threshold {
+            break;
+        }
+        if flags.strip_unlikelys {
+            flags.strip_unlikelys = false;
+        } else if flags.weight_classes {
+            flags.weight_classes = false;
+        } else if flags.clean_conditionally {
+            flags.clean_conditionally = false;
+        } else {
+            break;
+        }
+    }
+
+    // Attempts are in order, so the first of the longest ones is the strictest
+    let best = attempts.iter().map(|a| a.text_length).max()?;
+    let article = attempts.into_iter().find(|a| a.text_length == best)?;
+    (!article.content.is_empty()).then_some(article)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_extract_regular_article() {
+        let html = fs::read_to_string("./test-data/regular-article.html").unwrap();
+        let document = Html::parse_document(&html);
+        let article =
+            extract(&document, "https://foo.bar", &ReadabilityOptions::default()).unwrap();
+
+        assert_eq!(article.title, "Article title here - ArticleParser");
+        assert!(article.content.contains("rational peach"));
+        assert!(
+            article
+                .content
+                .contains(r#"<a href="https://foo.bar/dict/watermelon">"#)
+        );
+        assert!(
+            article
+                .content
+                .contains(r#"<img src="https://foo.bar/orange.png">"#)
+        );
+        assert!(!article.content.contains("Navigation here"));
+        assert!(!article.content.contains("Some widget here"));
+        assert!(!article.content.contains("Page footer here"));
+
+        let top = &article.candidates[0];
+        assert!(top.path.ends_with("article"), "top candidate: {}", top.path);
+        assert!(
+            article
+                .candidates
+                .windows(2)
+                .all(|w| w[0].score >= w[1].score)
+        );
+    }
+
+    #[test]
+    fn test_extract_strips_unlikely_candidates() {
+        let html = r#"
+            <html><body>
+                <div class="sidebar">Sidebar text that is long enough to be scored, with commas, and more commas.</div>
+                <div id="story">
+                    <h2 id="background">Background</h2>
+                    <p>First paragraph of the story is long enough, with a comma, to be scored by the algorithm.</p>
+                    <p>Second paragraph of the story is long enough, with a comma, to be scored as well.</p>
+                </div>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let options = ReadabilityOptions {
+            char_threshold: 100,
+            ..Default::default()
+        };
+        let article = extract(&document, "", &options).unwrap();
+
+        assert!(article.content.contains("First paragraph"));
+        assert!(article.content.contains("Second paragraph"));
+        assert!(!article.content.contains("Sidebar"));
+        // Anchor targets survive
+        assert!(article.content.contains(r#"<h2 id="background">"#));
+    }
+
+    #[test]
+    fn test_extract_tunable_weights() {
+        let html = r#"
+            <html><body>
+                <div class="story"><p>Short story paragraph, which still scores a bit.</p></div>
+                <div class="promo">
+                    <p>Promoted paragraph with a lot more text, commas, and length, so it wins on raw score alone.</p>
+                    <p>Another promoted paragraph with text, commas, and more length to outweigh the story.</p>
+                </div>
+            </body></html>
+        "#;
+        let document = Html::parse_document(html);
+        let options = ReadabilityOptions {
+            char_threshold: 0,
+            class_weight: 100.0,
+            positive_pattern: "story".to_string(),
+            ..Default::default()
+        };
+        let article = extract(&document, "", &options).unwrap();
+        assert!(article.candidates[0].path.contains("div.story"));
+        assert!(article.content.contains("Short story paragraph"));
+        assert!(!article.content.contains("Promoted paragraph"));
+    }
+
+    #[test]
+    fn test_is_data_table() {
+        let html = r#"
+            <table id="data"><thead><tr><th>Year</th><th>Revenue</th></tr></thead>
+                <tbody><tr><td>2021</td><td>10</td></tr></tbody></table>
+            <table id="layout" role="presentation"><tr><td>Left</td><td>Right</td></tr></table>
+        "#;
+        let document = Html::parse_fragment(html);
+        let select = |s: &str| {
+            document
+                .select(&Selector::parse(s).unwrap())
+                .next()
+                .unwrap()
+        };
+
+        assert!(is_data_table(&select("#data")));
+        assert!(!is_data_table(&select("#layout")));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::error::{AppResult as Result, Error};
+use crate::html::get_charset;
+use encoding_rs::Encoding;
 use reqwest::{header::{HeaderMap, HeaderValue}, Client, Proxy};
 
 async fn profetch(
@@ -21,7 +23,12 @@ async fn profetch(
     Ok(request)
 }
 
-pub async fn retrieve(url: &str, options: Option<RetrieveOptions>) -> Result<Vec<u8>> {
+// Send a GET request with the configured headers or proxy, failing on error statuses
+async fn send(
+    url: &str,
+    options: Option<RetrieveOptions>,
+    extra_headers: Option<HeaderMap>,
+) -> Result<reqwest::Response> {
     let default_headers = {
         let mut headers = HeaderMap::new();
         headers.insert(
@@ -34,7 +41,8 @@ pub async fn retrieve(url: &str, options: Option<RetrieveOptions>) -> Result<Vec
     };
 
     let options = options.unwrap_or_default();
-    let headers = options.headers.unwrap_or(default_headers);
+    let mut headers = options.headers.unwrap_or(default_headers);
+    headers.extend(extra_headers.unwrap_or_default());
 
     let client = Client::new();
     let res = if let Some(proxy) = options.proxy {
@@ -52,9 +60,72 @@ pub async fn retrieve(url: &str, options: Option<RetrieveOptions>) -> Result<Vec
     if status.is_client_error() || status.is_server_error() {
         return Err(Error::RequestFailedError(status));
     }
+    Ok(res)
+}
+
+pub async fn retrieve(url: &str, options: Option<RetrieveOptions>) -> Result<Vec<u8>> {
+    let (bytes, _) = retrieve_with_type(url, options).await?;
+    Ok(bytes)
+}
+
+/// Retrieve a resource along with its `Content-Type` header
+pub async fn retrieve_with_type(
+    url: &str,
+    options: Option<RetrieveOptions>,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let res = send(url, options, None).await?;
+    let content_type = res
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let bytes = res.bytes().await.map_err(Error::ReqwestError)?;
+    Ok((bytes.to_vec(), content_type))
+}
+
+/// Retrieve at most the first `max_bytes` of a resource.
+///
+/// A `Range` request is sent; servers that ignore it get their body read until
+/// `max_bytes` and the connection dropped.
+pub async fn retrieve_prefix(
+    url: &str,
+    max_bytes: usize,
+    options: Option<RetrieveOptions>,
+) -> Result<Vec<u8>> {
+    let mut range = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&format!("bytes=0-{}", max_bytes.saturating_sub(1))) {
+        range.insert("range", value);
+    }
+    let mut res = send(url, options, Some(range)).await?;
 
-    let bytes = res.bytes().await.map_err(|e| Error::ReqwestError(e))?;
-    Ok(bytes.to_vec())
+    let mut buffer = Vec::with_capacity(max_bytes.min(64 * 1024));
+    while buffer.len() < max_bytes {
+        match res.chunk().await.map_err(Error::ReqwestError)? {
+            Some(chunk) => buffer.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    buffer.truncate(max_bytes);
+    Ok(buffer)
+}
+
+/// Retrieve a page and decode it with the charset declared in its markup
+pub async fn retrieve_html(url: &str, options: Option<RetrieveOptions>) -> Result<String> {
+    let buffer = retrieve(url, options).await?;
+    decode_html(&buffer, url)
+}
+
+/// Decode the bytes of a page with the charset declared in its markup
+pub fn decode_html(buffer: &[u8], url: &str) -> Result<String> {
+    let text = String::from_utf8_lossy(buffer);
+    if text.trim().is_empty() {
+        return Err(Error::NullError(url.to_string()));
+    }
+    let charset = get_charset(&text);
+    match Encoding::for_label(charset.as_bytes()) {
+        Some(encoding) => Ok(encoding.decode(buffer).0.into_owned()),
+        None => Err(Error::UnsupportedEncoding(charset)),
+    }
 }
 
 #[derive(Default)]
@@ -65,7 +136,19 @@ pub struct RetrieveOptions {
     pub signal: Option<reqwest::Request>,
 }
 
-#[derive(Default)]
+// Requests with a streaming body cannot be cloned, the clone goes without one
+impl Clone for RetrieveOptions {
+    fn clone(&self) -> Self {
+        Self {
+            headers: self.headers.clone(),
+            proxy: self.proxy.clone(),
+            agent: self.agent.clone(),
+            signal: self.signal.as_ref().and_then(|r| r.try_clone()),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct ProxyOptions {
     target: String,
     pub headers: Option<HeaderMap>,
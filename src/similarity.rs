@@ -12,7 +12,7 @@ fn are_args_valid(main_string: &str, target_strings: &[String]) -> bool {
 /// https://webdocs.cs.ualberta.ca/~kondrak/papers/spire05.pdf
 /// The Bigram algorithm is particularly useful in scenarios where exact matches are not required, but rather, a degree of similarity or closeness between strings is the goal. 
 /// This can be beneficial in applications such as fuzzy matching in databases, spell checking, plagiarism detection.
-fn compare_two_strings(first: &str, second: &str) -> f64 {
+pub fn compare_two_strings(first: &str, second: &str) -> f64 {
     let first: String = first.chars().filter(|c| !c.is_whitespace()).collect();
     let second: String = second.chars().filter(|c| !c.is_whitespace()).collect();
 
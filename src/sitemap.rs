@@ -0,0 +1,388 @@
+use crate::error::{AppResult as Result, Error};
+use crate::linker::{absolutify, is_valid_url};
+use crate::retrieve::{retrieve, retrieve_prefix, RetrieveOptions};
+use flate2::read::GzDecoder;
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::io::Read;
+
+/// Limits of a sitemap crawl
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SitemapOptions {
+    /// Levels of sitemap indexes followed below the starting sitemaps
+    pub max_depth: usize,
+    /// Sitemap documents fetched over the whole crawl
+    pub max_sitemaps: usize,
+    /// URLs collected before the crawl stops
+    pub max_urls: usize,
+    /// Largest sitemap read, compressed or not; larger ones fail. The protocol
+    /// caps files at 50MB
+    pub max_bytes: usize,
+}
+
+impl Default for SitemapOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_sitemaps: 50,
+            max_urls: 50_000,
+            max_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// Google News extension of a sitemap URL
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NewsInfo {
+    pub title: Option<String>,
+    pub publication_date: Option<String>,
+    pub publication_name: Option<String>,
+    pub language: Option<String>,
+}
+
+/// A page listed in a sitemap. Dates are kept as written, in W3C datetime format
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SitemapEntry {
+    pub url: String,
+    pub lastmod: Option<String>,
+    pub changefreq: Option<String>,
+    pub priority: Option<f32>,
+    pub news: Option<NewsInfo>,
+}
+
+impl SitemapEntry {
+    /// Last modification, or the news publication date
+    pub fn modified(&self) -> Option<&str> {
+        self.lastmod
+            .as_deref()
+            .or(self.news.as_ref().and_then(|n| n.publication_date.as_deref()))
+    }
+
+    /// Whether the entry changed on or after `date` (`2025-06-01`, `2025-06-01T12:00:00Z`...).
+    ///
+    /// W3C dates compare as strings as long as they share a timezone; entries
+    /// without a date are kept.
+    pub fn modified_since(&self, date: &str) -> bool {
+        self.modified().is_none_or(|modified| modified >= date)
+    }
+}
+
+/// A sitemap referenced by a sitemap index
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SitemapRef {
+    pub url: String,
+    pub lastmod: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Sitemap {
+    /// `<urlset>`, or a plain text list of URLs
+    UrlSet(Vec<SitemapEntry>),
+    /// `<sitemapindex>`
+    Index(Vec<SitemapRef>),
+}
+
+/// URLs of the `Sitemap:` lines of a robots.txt
+pub fn robots_sitemaps(robots_txt: &str, base_url: &str) -> Vec<String> {
+    let mut sitemaps: Vec<String> = Vec::new();
+    for line in robots_txt.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if let Some((key, value)) = line.split_once(':')
+            && key.trim().eq_ignore_ascii_case("sitemap")
+        {
+            let url = absolutify(base_url, value.trim());
+            if is_valid_url(&url) && !sitemaps.contains(&url) {
+                sitemaps.push(url);
+            }
+        }
+    }
+    sitemaps
+}
+
+fn too_large(max_bytes: usize) -> Error {
+    Error::AppError(format!("Sitemap too large, over {} bytes", max_bytes))
+}
+
+/// Inflate gzip data, other data is returned as is.
+///
+/// Fails rather than truncating when the result is over `max_bytes`.
+pub fn decompress(bytes: Vec<u8>, max_bytes: usize) -> Result<Vec<u8>> {
+    if !bytes.starts_with(&[0x1f, 0x8b]) {
+        if bytes.len() > max_bytes {
+            return Err(too_large(max_bytes));
+        }
+        return Ok(bytes);
+    }
+    let mut inflated = Vec::new();
+    // One byte past the limit tells a sitemap that fits from one that was cut
+    GzDecoder::new(bytes.as_slice())
+        .take((max_bytes as u64).saturating_add(1))
+        .read_to_end(&mut inflated)
+        .map_err(|e| Error::AppError(format!("Invalid gzip sitemap: {}", e)))?;
+    if inflated.len() > max_bytes {
+        return Err(too_large(max_bytes));
+    }
+    Ok(inflated)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|c| c.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+fn news_info(url: Node) -> Option<NewsInfo> {
+    let news = child(url, "news")?;
+    let publication = child(news, "publication");
+    Some(NewsInfo {
+        title: child_text(news, "title"),
+        publication_date: child_text(news, "publication_date"),
+        publication_name: publication.and_then(|p| child_text(p, "name")),
+        language: publication.and_then(|p| child_text(p, "language")),
+    })
+}
+
+/// Parse a sitemap or a sitemap index; relative locations are resolved against `sitemap_url`
+pub fn parse_sitemap(text: &str, sitemap_url: &str) -> Result<Sitemap> {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if !text.starts_with('<') {
+        let entries = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| is_valid_url(line))
+            .map(|line| SitemapEntry {
+                url: line.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        return Ok(Sitemap::UrlSet(entries));
+    }
+
+    let document = Document::parse(text)
+        .map_err(|e| Error::AppError(format!("Invalid sitemap {}: {}", sitemap_url, e)))?;
+    let root = document.root_element();
+    let location = |node: Node| {
+        child_text(node, "loc")
+            .map(|loc| absolutify(sitemap_url, &loc))
+            .filter(|loc| is_valid_url(loc))
+    };
+    match root.tag_name().name() {
+        "urlset" => Ok(Sitemap::UrlSet(
+            root.children()
+                .filter(|c| c.is_element() && c.tag_name().name() == "url")
+                .filter_map(|url| {
+                    Some(SitemapEntry {
+                        url: location(url)?,
+                        lastmod: child_text(url, "lastmod"),
+                        changefreq: child_text(url, "changefreq"),
+                        priority: child_text(url, "priority").and_then(|p| p.parse().ok()),
+                        news: news_info(url),
+                    })
+                })
+                .collect(),
+        )),
+        "sitemapindex" => Ok(Sitemap::Index(
+            root.children()
+                .filter(|c| c.is_element() && c.tag_name().name() == "sitemap")
+                .filter_map(|sitemap| {
+                    Some(SitemapRef {
+                        url: location(sitemap)?,
+                        lastmod: child_text(sitemap, "lastmod"),
+                    })
+                })
+                .collect(),
+        )),
+        other => Err(Error::AppError(format!("Not a sitemap {}: <{}>", sitemap_url, other))),
+    }
+}
+
+/// Fetch a sitemap, inflating it when gzipped.
+///
+/// At most `max_bytes` are downloaded; a larger sitemap is an error.
+pub async fn fetch_sitemap(
+    url: &str,
+    options: &SitemapOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> Result<Sitemap> {
+    let bytes = retrieve_prefix(url, options.max_bytes.saturating_add(1), fetch_opts).await?;
+    // Inflated, a gzipped sitemap is only larger
+    if bytes.len() > options.max_bytes {
+        return Err(too_large(options.max_bytes));
+    }
+    let bytes = decompress(bytes, options.max_bytes)?;
+    parse_sitemap(&String::from_utf8_lossy(&bytes), url)
+}
+
+/// Collect the URLs of the given sitemaps, following sitemap indexes within the limits.
+///
+/// Sitemaps that fail to fetch or parse are skipped.
+pub async fn crawl_sitemaps(
+    sitemaps: &[String],
+    options: &SitemapOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> Vec<SitemapEntry> {
+    let mut queue: VecDeque<(String, usize)> = sitemaps.iter().map(|url| (url.clone(), 0)).collect();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut seen_urls: HashSet<String> = HashSet::new();
+    let mut entries = Vec::new();
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if visited.len() >= options.max_sitemaps || entries.len() >= options.max_urls {
+            break;
+        }
+        if !visited.insert(url.clone()) {
+            continue;
+        }
+        match fetch_sitemap(&url, options, fetch_opts.clone()).await {
+            Ok(Sitemap::UrlSet(urls)) => {
+                for entry in urls {
+                    if entries.len() >= options.max_urls {
+                        break;
+                    }
+                    if seen_urls.insert(entry.url.clone()) {
+                        entries.push(entry);
+                    }
+                }
+            }
+            Ok(Sitemap::Index(children)) if depth < options.max_depth => {
+                queue.extend(children.into_iter().map(|child| (child.url, depth + 1)));
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Collect the URLs of a site from the sitemaps its robots.txt lists,
+/// or from `/sitemap.xml` when it lists none
+pub async fn site_sitemap(
+    site_url: &str,
+    options: &SitemapOptions,
+    fetch_opts: Option<RetrieveOptions>,
+) -> Result<Vec<SitemapEntry>> {
+    if !is_valid_url(site_url) {
+        return Err(Error::NullError("Site URL".to_string()));
+    }
+    let robots_url = absolutify(site_url, "/robots.txt");
+    let mut sitemaps = match retrieve(&robots_url, fetch_opts.clone()).await {
+        Ok(bytes) => robots_sitemaps(&String::from_utf8_lossy(&bytes), site_url),
+        Err(_) => Vec::new(),
+    };
+    if sitemaps.is_empty() {
+        sitemaps.push(absolutify(site_url, "/sitemap.xml"));
+    }
+    Ok(crawl_sitemaps(&sitemaps, options, fetch_opts).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const NEWS_SITEMAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+                xmlns:news="http://www.google.com/schemas/sitemap-news/0.9">
+          <url>
+            <loc>https://somewhere.com/news/first</loc>
+            <lastmod>2025-06-10</lastmod>
+            <changefreq>daily</changefreq>
+            <priority>0.8</priority>
+          </url>
+          <url>
+            <loc>/news/second</loc>
+            <news:news>
+              <news:publication>
+                <news:name>Somewhere Daily</news:name>
+                <news:language>en</news:language>
+              </news:publication>
+              <news:publication_date>2025-06-12T08:00:00+00:00</news:publication_date>
+              <news:title>Second story</news:title>
+            </news:news>
+          </url>
+          <url><lastmod>2025-06-12</lastmod></url>
+        </urlset>"#;
+
+    #[test]
+    fn test_robots_sitemaps() {
+        let robots = "User-agent: *\nDisallow: /admin\n\nSitemap: https://somewhere.com/sitemap_index.xml\nsitemap: /news-sitemap.xml # news\nSitemap: https://somewhere.com/sitemap_index.xml\n";
+        assert_eq!(
+            robots_sitemaps(robots, "https://somewhere.com/"),
+            vec![
+                "https://somewhere.com/sitemap_index.xml",
+                "https://somewhere.com/news-sitemap.xml",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_urlset() {
+        let Sitemap::UrlSet(entries) = parse_sitemap(NEWS_SITEMAP, "https://somewhere.com/sitemap.xml").unwrap() else {
+            panic!("expected a urlset");
+        };
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].lastmod.as_deref(), Some("2025-06-10"));
+        assert_eq!(entries[0].priority, Some(0.8));
+        assert_eq!(entries[1].url, "https://somewhere.com/news/second");
+        let news = entries[1].news.as_ref().unwrap();
+        assert_eq!(news.title.as_deref(), Some("Second story"));
+        assert_eq!(news.publication_name.as_deref(), Some("Somewhere Daily"));
+        assert_eq!(entries[1].modified(), Some("2025-06-12T08:00:00+00:00"));
+
+        assert!(!entries[0].modified_since("2025-06-11"));
+        assert!(entries[1].modified_since("2025-06-11"));
+    }
+
+    #[test]
+    fn test_parse_index_and_text() {
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <sitemap><loc>https://somewhere.com/sitemap-1.xml.gz</loc><lastmod>2025-06-01</lastmod></sitemap>
+              <sitemap><loc>https://somewhere.com/sitemap-2.xml</loc></sitemap>
+            </sitemapindex>"#;
+        assert_eq!(
+            parse_sitemap(index, "https://somewhere.com/sitemap.xml").unwrap(),
+            Sitemap::Index(vec![
+                SitemapRef {
+                    url: "https://somewhere.com/sitemap-1.xml.gz".to_string(),
+                    lastmod: Some("2025-06-01".to_string()),
+                },
+                SitemapRef {
+                    url: "https://somewhere.com/sitemap-2.xml".to_string(),
+                    lastmod: None,
+                },
+            ])
+        );
+
+        let Sitemap::UrlSet(entries) =
+            parse_sitemap("https://somewhere.com/a\nnot a url\nhttps://somewhere.com/b\n", "https://somewhere.com/sitemap.txt")
+                .unwrap()
+        else {
+            panic!("expected a urlset");
+        };
+        assert_eq!(entries.len(), 2);
+
+        assert!(parse_sitemap("<html></html>", "https://somewhere.com/sitemap.xml").is_err());
+    }
+
+    #[test]
+    fn test_decompress() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(NEWS_SITEMAP.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let inflated = decompress(gzipped.clone(), usize::MAX).unwrap();
+        assert_eq!(inflated, NEWS_SITEMAP.as_bytes());
+        assert!(decompress(gzipped.clone(), 10).is_err());
+        assert_eq!(decompress(gzipped, NEWS_SITEMAP.len()).unwrap(), NEWS_SITEMAP.as_bytes());
+        assert_eq!(decompress(b"plain".to_vec(), 10).unwrap(), b"plain");
+        assert!(decompress(b"plain text over the limit".to_vec(), 10).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::extract::extract_with_readability_options;
+use crate::profile::{strip_selectors, SiteProfile};
+use crate::readability::ReadabilityOptions;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A way of locating the main content of a page.
+///
+/// `ParseOptions::strategies` lists the strategies tried in order; the first
+/// candidate with enough text wins and is recorded on `ParsedContent::strategy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractionStrategy {
+    /// The `content` selector of the matching site profile
+    SiteProfile,
+    /// Content scoring, see `readability::extract`
+    #[default]
+    Readability,
+    /// `<article>`, `[itemprop=articleBody]` or `<main>` containers
+    Semantic,
+    /// The block holding the most prose with the fewest links
+    TextDensity,
+    /// The body of an AMP document
+    Amp,
+    /// The meta description as a single paragraph
+    MetaDescription,
+}
+
+/// Text the meta description needs to be accepted; it is the last resort and
+/// is never as long as `content_len_threshold`
+pub const META_DESCRIPTION_MIN_LEN: usize = 1;
+
+impl ExtractionStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SiteProfile => "site_profile",
+            Self::Readability => "readability",
+            Self::Semantic => "semantic",
+            Self::TextDensity => "text_density",
+            Self::Amp => "amp",
+            Self::MetaDescription => "meta_description",
+        }
+    }
+
+    /// Characters of text a candidate of this strategy needs to win
+    pub fn min_text_length(&self, content_len_threshold: usize) -> usize {
+        match self {
+            Self::MetaDescription => META_DESCRIPTION_MIN_LEN,
+            _ => content_len_threshold,
+        }
+    }
+}
+
+impl fmt::Display for ExtractionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The full chain, in the order the strategies are tried by default
+pub fn default_strategies() -> Vec<ExtractionStrategy> {
+    vec![
+        ExtractionStrategy::SiteProfile,
+        ExtractionStrategy::Readability,
+        ExtractionStrategy::Semantic,
+        ExtractionStrategy::TextDensity,
+        ExtractionStrategy::Amp,
+        ExtractionStrategy::MetaDescription,
+    ]
+}
+
+/// Everything a strategy may need to produce a candidate
+pub struct StrategyInput<'a> {
+    pub html: &'a str,
+    pub url: &'a str,
+    pub profile: Option<&'a SiteProfile>,
+    pub description: &'a str,
+    pub readability: &'a ReadabilityOptions,
+}
+
+const SEMANTIC_SELECTORS: &[&str] = &["article", "[itemprop=articleBody]", "main"];
+
+const DENSITY_CONTAINERS: &[&str] = &["div", "section", "article", "main", "td", "body"];
+
+// Elements that never carry the article body of an AMP page
+const AMP_JUNK: &[&str] = &[
+    "amp-analytics",
+    "amp-pixel",
+    "amp-ad",
+    "amp-sticky-ad",
+    "amp-sidebar",
+    "amp-consent",
+    "amp-user-notification",
+    "amp-geo",
+    "header",
+    "footer",
+    "nav",
+    "script",
+    "style",
+];
+
+fn text_length(element: &ElementRef) -> usize {
+    element.text().map(|t| t.trim().chars().count()).sum()
+}
+
+fn link_text_length(element: &ElementRef) -> usize {
+    let selector = Selector::parse("a").unwrap();
+    element.select(&selector).map(|a| text_length(&a)).sum()
+}
+
+fn semantic_container(document: &Html) -> Option<String> {
+    SEMANTIC_SELECTORS.iter().find_map(|s| {
+        let selector = Selector::parse(s).unwrap();
+        document
+            .select(&selector)
+            .map(|e| (text_length(&e), e))
+            .filter(|(length, _)| *length > 0)
+            .max_by_key(|(length, _)| *length)
+            .map(|(_, e)| e.inner_html())
+    })
+}
+
+// Text held directly by the element, without descending into nested containers
+fn own_text_length(element: &ElementRef) -> usize {
+    element
+        .children()
+        .map(|child| match child.value() {
+            Node::Text(t) => t.trim().chars().count(),
+            Node::Element(e) if !DENSITY_CONTAINERS.contains(&e.name()) => {
+                ElementRef::wrap(child).map_or(0, |c| text_length(&c))
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+fn densest_block(document: &Html) -> Option<String> {
+    let selector = Selector::parse(&DENSITY_CONTAINERS.join(", ")).unwrap();
+    document
+        .select(&selector)
+        .filter_map(|e| {
+            let length = text_length(&e);
+            if length == 0 {
+                return None;
+            }
+            let link_density = link_text_length(&e) as f64 / length as f64;
+            let score = own_text_length(&e) as f64 * (1.0 - link_density);
+            (score > 0.0).then_some((score, e))
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, e)| e.inner_html())
+}
+
+/// Whether the document declares itself as AMP with `<html amp>` or `<html ⚡>`
+pub fn is_amp_document(document: &Html) -> bool {
+    let selector = Selector::parse("html").unwrap();
+    document
+        .select(&selector)
+        .next()
+        .is_some_and(|html| html.value().attrs().any(|(name, _)| name == "amp" || name == "⚡"))
+}
+
+fn amp_body(html: &str) -> Option<String> {
+    if !is_amp_document(&Html::parse_document(html)) {
+        return None;
+    }
+    let junk: Vec<String> = AMP_JUNK.iter().map(|s| s.to_string()).collect();
+    let document = Html::parse_document(&strip_selectors(html, &junk));
+    let selector = Selector::parse("body").unwrap();
+    document.select(&selector).next().map(|body| body.inner_html())
+}
+
+fn meta_description(description: &str) -> Option<String> {
+    let description = description.trim();
+    if description.is_empty() {
+        return None;
+    }
+    let escaped = description
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    Some(format!("<p>{}</p>", escaped))
+}
+
+/// Produce the content candidate of a single strategy, if it finds anything
+pub fn run_strategy(strategy: ExtractionStrategy, input: &StrategyInput) -> Option<String> {
+    match strategy {
+        ExtractionStrategy::SiteProfile => input.profile?.extract(input.html).content,
+        ExtractionStrategy::Readability => {
+            extract_with_readability_options(input.html, input.url, input.readability)
+        }
+        ExtractionStrategy::Semantic => semantic_container(&Html::parse_document(input.html)),
+        ExtractionStrategy::TextDensity => densest_block(&Html::parse_document(input.html)),
+        ExtractionStrategy::Amp => amp_body(input.html),
+        ExtractionStrategy::MetaDescription => meta_description(input.description),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input<'a>(html: &'a str, readability: &'a ReadabilityOptions) -> StrategyInput<'a> {
+        StrategyInput {
+            html,
+            url: "https://some.where/launch",
+            profile: None,
+            description: "Our new <widget> ships today",
+            readability,
+        }
+    }
+
+    #[test]
+    fn test_semantic_strategy() {
+        let html = r#"
+            <html><body>
+                <nav>Home | Products</nav>
+                <main><div itemprop="articleBody"><p>The body copy.</p></div></main>
+            </body></html>
+        "#;
+        let options = ReadabilityOptions::default();
+        let content = run_strategy(ExtractionStrategy::Semantic, &input(html, &options)).unwrap();
+        assert!(content.contains("The body copy."));
+        assert!(!content.contains("Products"));
+    }
+
+    #[test]
+    fn test_text_density_strategy() {
+        let html = r#"
+            <html><body>
+                <div class="links"><a href="/a">A link</a> <a href="/b">Another link</a></div>
+                <div class="copy">Plain prose without any markup, long enough to win the density contest.</div>
+            </body></html>
+        "#;
+        let options = ReadabilityOptions::default();
+        let content =
+            run_strategy(ExtractionStrategy::TextDensity, &input(html, &options)).unwrap();
+        assert!(content.contains("Plain prose"));
+        assert!(!content.contains("Another link"));
+    }
+
+    #[test]
+    fn test_amp_strategy() {
+        let options = ReadabilityOptions::default();
+        let regular = "<html><body><p>Text</p></body></html>";
+        assert!(run_strategy(ExtractionStrategy::Amp, &input(regular, &options)).is_none());
+
+        let amp = r#"<html ⚡><body><amp-analytics></amp-analytics><header>Logo</header><p>AMP text</p></body></html>"#;
+        let content = run_strategy(ExtractionStrategy::Amp, &input(amp, &options)).unwrap();
+        assert!(content.contains("AMP text"));
+        assert!(!content.contains("Logo"));
+        assert!(!content.contains("amp-analytics"));
+    }
+
+    #[test]
+    fn test_meta_description_strategy() {
+        let options = ReadabilityOptions::default();
+        let content =
+            run_strategy(ExtractionStrategy::MetaDescription, &input("", &options)).unwrap();
+        assert_eq!(content, "<p>Our new &lt;widget&gt; ships today</p>");
+        assert!(run_strategy(ExtractionStrategy::SiteProfile, &input("", &options)).is_none());
+    }
+}
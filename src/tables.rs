@@ -0,0 +1,288 @@
+use crate::readability::is_data_table;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+
+/// Largest `colspan`/`rowspan` honoured, guarding against bogus markup
+const MAX_SPAN: usize = 100;
+
+/// A data table of the extracted content, with spanning cells expanded
+/// so every row has one value per column
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DataTable {
+    pub caption: Option<String>,
+    /// Column headers; several header rows are joined with " / "
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+fn normalize_text(element: &ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn span(cell: &ElementRef, name: &str) -> usize {
+    cell.attr(name)
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(1usize)
+        .clamp(1, MAX_SPAN)
+}
+
+// Whether the element belongs to `table` itself rather than to a nested table
+fn owned_by(element: &ElementRef, table: &ElementRef) -> bool {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|a| a.value().name() == "table")
+        .is_some_and(|a| a.id() == table.id())
+}
+
+struct GridRow {
+    header: bool,
+    cells: Vec<String>,
+}
+
+// Lay the rows out on a grid, copying spanning cells into every slot they cover
+fn expand(table: &ElementRef) -> Vec<GridRow> {
+    let selector = Selector::parse("tr").unwrap();
+    let mut grid: Vec<Vec<Option<String>>> = Vec::new();
+    let mut headers = Vec::new();
+    for (r, row) in table
+        .select(&selector)
+        .filter(|row| owned_by(row, table))
+        .enumerate()
+    {
+        if grid.len() <= r {
+            grid.resize(r + 1, Vec::new());
+        }
+        let cells: Vec<ElementRef> = row
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|c| matches!(c.value().name(), "td" | "th"))
+            .collect();
+        let in_head = row
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .take_while(|a| a.id() != table.id())
+            .any(|a| a.value().name() == "thead");
+        headers.push(in_head || (!cells.is_empty() && cells.iter().all(|c| c.value().name() == "th")));
+
+        let mut column = 0;
+        for cell in cells {
+            while grid[r].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+            let text = normalize_text(&cell);
+            let (colspan, rowspan) = (span(&cell, "colspan"), span(&cell, "rowspan"));
+            for dr in 0..rowspan {
+                if grid.len() <= r + dr {
+                    grid.resize(r + dr + 1, Vec::new());
+                }
+                let target = &mut grid[r + dr];
+                if target.len() < column + colspan {
+                    target.resize(column + colspan, None);
+                }
+                for slot in &mut target[column..column + colspan] {
+                    *slot = Some(text.clone());
+                }
+            }
+            column += colspan;
+        }
+    }
+
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    grid.into_iter()
+        .enumerate()
+        // Rows only reached by a rowspan past the last <tr> are dropped
+        .filter(|(r, _)| *r < headers.len())
+        .map(|(r, cells)| {
+            let mut cells: Vec<String> = cells.into_iter().map(Option::unwrap_or_default).collect();
+            cells.resize(width, String::new());
+            GridRow {
+                header: headers[r],
+                cells,
+            }
+        })
+        .filter(|row| row.cells.iter().any(|c| !c.is_empty()))
+        .collect()
+}
+
+// A first row made only of bold cells acts as the header of tables without <th>
+fn bold_first_row(table: &ElementRef) -> bool {
+    let row_selector = Selector::parse("tr").unwrap();
+    let bold_selector = Selector::parse("b, strong").unwrap();
+    let Some(row) = table.select(&row_selector).find(|row| owned_by(row, table)) else {
+        return false;
+    };
+    let cells: Vec<ElementRef> = row.children().filter_map(ElementRef::wrap).collect();
+    !cells.is_empty()
+        && cells.iter().all(|cell| {
+            let text = normalize_text(cell);
+            let bold: String = cell.select(&bold_selector).map(|b| normalize_text(&b)).collect();
+            !text.is_empty() && text.replace(' ', "") == bold.replace(' ', "")
+        })
+}
+
+/// Turn a `<table>` element into a `DataTable`.
+///
+/// Leading header rows (`thead`, rows of `th`, or a bold first row) become the
+/// headers; header rows further down are kept as data.
+pub fn parse_table(table: &ElementRef) -> DataTable {
+    let caption_selector = Selector::parse("caption").unwrap();
+    let caption = table
+        .select(&caption_selector)
+        .find(|c| owned_by(c, table))
+        .map(|c| normalize_text(&c))
+        .filter(|c| !c.is_empty());
+
+    let mut rows = expand(table);
+    if let Some(first) = rows.first_mut()
+        && !first.header
+        && bold_first_row(table)
+    {
+        first.header = true;
+    }
+    let head = rows.iter().take_while(|row| row.header).count();
+    // Ignore a table made only of header cells
+    let head = if head == rows.len() { 0 } else { head };
+    let width = rows.first().map_or(0, |row| row.cells.len());
+
+    let headers = if head == 0 {
+        Vec::new()
+    } else {
+        (0..width)
+            .map(|column| {
+                let mut parts: Vec<&str> = Vec::new();
+                for row in &rows[..head] {
+                    let value = row.cells[column].as_str();
+                    if !value.is_empty() && parts.last() != Some(&value) {
+                        parts.push(value);
+                    }
+                }
+                parts.join(" / ")
+            })
+            .collect()
+    };
+
+    DataTable {
+        caption,
+        headers,
+        rows: rows.drain(head..).map(|row| row.cells).collect(),
+    }
+}
+
+/// Extract the data tables of an HTML fragment, such as `ParsedContent::content`.
+///
+/// Layout tables, as told apart by `readability::is_data_table`, are skipped.
+pub fn extract_tables(html: &str) -> Vec<DataTable> {
+    let fragment = Html::parse_fragment(html);
+    let selector = Selector::parse("table").unwrap();
+    fragment
+        .select(&selector)
+        .filter(is_data_table)
+        .map(|table| parse_table(&table))
+        .filter(|table| !table.rows.is_empty())
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl DataTable {
+    /// Render the table as CSV, headers first when there are any
+    pub fn to_csv(&self) -> String {
+        let mut lines = Vec::new();
+        if !self.headers.is_empty() {
+            lines.push(&self.headers);
+        }
+        lines.extend(&self.rows);
+        lines
+            .iter()
+            .map(|row| row.iter().map(|v| csv_field(v)).collect::<Vec<_>>().join(","))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Render the table as a JSON object with `caption`, `headers` and `rows`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EARNINGS: &str = r#"
+        <table>
+            <caption>Quarterly results</caption>
+            <thead>
+                <tr><th rowspan="2">Segment</th><th colspan="2">Revenue</th></tr>
+                <tr><th>Q1</th><th>Q2</th></tr>
+            </thead>
+            <tbody>
+                <tr><td rowspan="2">Cloud</td><td>1,200</td><td>1,350</td></tr>
+                <tr><td>"adj." 1,190</td><td>1,340</td></tr>
+                <tr><td>Devices</td><td colspan="2">n/a</td></tr>
+            </tbody>
+        </table>
+    "#;
+
+    #[test]
+    fn test_extract_tables() {
+        let tables = extract_tables(EARNINGS);
+        assert_eq!(tables.len(), 1);
+        let table = &tables[0];
+
+        assert_eq!(table.caption.as_deref(), Some("Quarterly results"));
+        assert_eq!(table.headers, vec!["Segment", "Revenue / Q1", "Revenue / Q2"]);
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["Cloud", "1,200", "1,350"],
+                vec!["Cloud", "\"adj.\" 1,190", "1,340"],
+                vec!["Devices", "n/a", "n/a"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bold_header_and_layout_tables() {
+        let html = r#"
+            <table><tr><td>Menu</td><td>Body</td></tr></table>
+            <table>
+                <tr><td><b>Name</b></td><td><strong>Age</strong></td><td><b>City</b></td></tr>
+                <tr><td>Ann</td><td>31</td><td>Oslo</td></tr>
+                <tr><td>Bo</td><td>42</td><td>Rome</td></tr>
+                <tr><td>Cy</td><td>27</td><td>Lima</td></tr>
+                <tr><td>Di</td><td>55</td><td>Kyiv</td></tr>
+            </table>
+        "#;
+        let tables = extract_tables(html);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].headers, vec!["Name", "Age", "City"]);
+        assert_eq!(tables[0].rows.len(), 4);
+    }
+
+    #[test]
+    fn test_export() {
+        let table = &extract_tables(EARNINGS)[0];
+        assert_eq!(
+            table.to_csv(),
+            "Segment,Revenue / Q1,Revenue / Q2\nCloud,\"1,200\",\"1,350\"\nCloud,\"\"\"adj.\"\" 1,190\",\"1,340\"\nDevices,n/a,n/a\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&table.to_json()).unwrap();
+        assert_eq!(json["headers"][1], "Revenue / Q1");
+        assert_eq!(json["rows"][2][0], "Devices");
+    }
+}
@@ -0,0 +1,202 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Query parameters known to only carry tracking data
+const DEFAULT_KEYS: &[&str] = &[
+    "__twitter_impression",
+    "_branch_match_id",
+    "_ga",
+    "_gl",
+    "_openstat",
+    "action_object_map",
+    "action_ref_map",
+    "action_type_map",
+    "cndid",
+    "dclid",
+    "fbclid",
+    "gbraid",
+    "gclid",
+    "gclsrc",
+    "gs_l",
+    "igsh",
+    "igshid",
+    "li_fat_id",
+    "mbid",
+    "mkt_tok",
+    "msclkid",
+    "oly_anon_id",
+    "oly_enc_id",
+    "rb_clickid",
+    "s_cid",
+    "si",
+    "spjobid",
+    "spmailingid",
+    "spreportid",
+    "spuserid",
+    "ttclid",
+    "twclid",
+    "vero_conv",
+    "vero_id",
+    "wbraid",
+    "wickedid",
+    "wt.mc_ev",
+    "wt.mc_id",
+    "wt.srch",
+    "wt_mc_o",
+    "yclid",
+];
+
+/// Parameter families stripped by prefix
+const DEFAULT_PREFIXES: &[&str] = &["utm_", "pk_", "mtm_", "mc_", "ga_", "hmb_", "fb_", "_hs"];
+
+/// Stripping rules overridden for one domain and its subdomains
+#[derive(Clone, Debug, Default)]
+pub struct DomainRules {
+    pub domain: String,
+    /// Extra keys stripped on this domain, e.g. `ref` or `amp`
+    pub strip: Vec<String>,
+    /// Keys kept on this domain even if a global rule matches them
+    pub keep: Vec<String>,
+}
+
+/// Which query parameters `purify` removes. Keys are compared case-insensitively.
+#[derive(Clone, Debug, Default)]
+pub struct TrackingRules {
+    /// Exact keys, lowercase
+    pub keys: HashSet<String>,
+    /// Key prefixes, `utm_` strips `utm_source`, `utm_medium`...
+    pub prefixes: Vec<String>,
+    /// Keys matching any of these are stripped
+    pub patterns: Vec<Regex>,
+    /// Keys never stripped, whatever the other rules say; lowercase
+    pub allow: HashSet<String>,
+    pub domains: Vec<DomainRules>,
+}
+
+impl TrackingRules {
+    /// The bundled rule set
+    pub fn defaults() -> Self {
+        Self {
+            keys: DEFAULT_KEYS.iter().map(|k| k.to_string()).collect(),
+            prefixes: DEFAULT_PREFIXES.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn domain_rules<'a>(&'a self, host: &'a str) -> impl Iterator<Item = &'a DomainRules> {
+        let host = host.trim_start_matches("www.");
+        self.domains.iter().filter(move |rules| {
+            let domain = rules.domain.trim_start_matches("www.");
+            host.eq_ignore_ascii_case(domain)
+                || host.to_lowercase().ends_with(&format!(".{}", domain.to_lowercase()))
+        })
+    }
+
+    /// Whether the parameter `key` is tracking data on `host`
+    pub fn is_tracking(&self, host: &str, key: &str) -> bool {
+        let key = key.to_lowercase();
+        let matches = |keys: &[String]| keys.iter().any(|k| k.eq_ignore_ascii_case(&key));
+        if self.allow.contains(&key) || self.domain_rules(host).any(|rules| matches(&rules.keep)) {
+            return false;
+        }
+        self.keys.contains(&key)
+            || self.prefixes.iter().any(|p| key.starts_with(&p.to_lowercase()))
+            || self.patterns.iter().any(|r| r.is_match(&key))
+            || self.domain_rules(host).any(|rules| matches(&rules.strip))
+    }
+
+    /// Remove the fragment and the tracking parameters of a URL, `None` when it does not parse
+    pub fn strip(&self, url: &str) -> Option<String> {
+        let mut parsed_url = url::Url::parse(url).ok()?;
+        parsed_url.set_fragment(None);
+        let host = parsed_url.host_str().unwrap_or("").to_string();
+
+        let kept: Vec<(String, String)> = parsed_url
+            .query_pairs()
+            .filter(|(k, _)| !self.is_tracking(&host, k))
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        if kept.is_empty() {
+            parsed_url.set_query(None);
+        } else {
+            let mut pairs = parsed_url.query_pairs_mut();
+            pairs.clear();
+            for (k, v) in kept {
+                pairs.append_pair(&k, &v);
+            }
+        }
+        Some(parsed_url.to_string())
+    }
+}
+
+lazy_static! {
+    static ref RULES: Mutex<TrackingRules> = Mutex::new(TrackingRules::defaults());
+}
+
+/// Replace the rules used by every extraction, returning the previous ones
+pub fn set_tracking_rules(rules: TrackingRules) -> TrackingRules {
+    std::mem::replace(&mut RULES.lock().unwrap(), rules)
+}
+
+pub fn get_tracking_rules() -> TrackingRules {
+    RULES.lock().unwrap().clone()
+}
+
+/// Restore the bundled rules
+pub fn reset_tracking_rules() {
+    set_tracking_rules(TrackingRules::defaults());
+}
+
+/// Strip a URL with the current rules
+pub fn strip_tracking(url: &str) -> Option<String> {
+    RULES.lock().unwrap().strip(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules() {
+        let rules = TrackingRules::defaults();
+        let entries = vec![
+            ("https://some.where/a?gclid=1&id=7&_ga=2.1&mc_cid=x&mc_eid=y", "https://some.where/a?id=7"),
+            ("https://some.where/a?igshid=abc&utm_source=ig&UTM_Medium=x", "https://some.where/a"),
+            ("https://youtu.be/abc?si=XyZ&t=42", "https://youtu.be/abc?t=42"),
+            ("https://some.where/a?WT.mc_id=1&fbclid=2&fb_ref=3", "https://some.where/a"),
+            // Legit parameters that an older list stripped
+            ("https://some.where/a?amp=1&referrer=home", "https://some.where/a?amp=1&referrer=home"),
+        ];
+        for (url, expected) in entries {
+            assert_eq!(rules.strip(url).as_deref(), Some(expected), "URL: {}", url);
+        }
+        assert_eq!(rules.strip("not a url"), None);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut rules = TrackingRules::defaults();
+        rules.patterns.push(Regex::new(r"^ref_?(src|url)?$").unwrap());
+        rules.allow.insert("utm_campaign".to_string());
+        rules.domains.push(DomainRules {
+            domain: "news.example".to_string(),
+            strip: vec!["amp".to_string()],
+            keep: vec!["si".to_string()],
+        });
+
+        assert_eq!(
+            rules.strip("https://some.where/a?ref_src=tw&utm_campaign=launch&utm_source=x").as_deref(),
+            Some("https://some.where/a?utm_campaign=launch")
+        );
+        assert_eq!(
+            rules.strip("https://www.news.example/a?amp=1&si=3").as_deref(),
+            Some("https://www.news.example/a?si=3")
+        );
+        assert_eq!(
+            rules.strip("https://other.example/a?amp=1&si=3").as_deref(),
+            Some("https://other.example/a?amp=1")
+        );
+    }
+}